@@ -0,0 +1,476 @@
+use crate::config::GameConfig;
+use crate::damage::{DamageEvent, DamageSource, DeathEvent, Hitpoints};
+use crate::definitions_units::{EnemyBundle, Player, PlayerStats, ENEMY_COLOR};
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::particles::{ParticleEffect, ParticleEvent};
+use crate::players::{self, FrameControls};
+use crate::popups::PopupEvent;
+use crate::powerups::Staggered;
+use crate::settings::{GameSettings, MusicChannel};
+use crate::theme::ThemedKind;
+use crate::{play_sound, AudioType, GameTickInfo, Score, TickEvent, SCORE_COLOR, TIME_STEP};
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+const BOSS_STING_ASSET_PATH: &str = "535641__saintsamael__bitcrush-bump.wav";
+const BOSS_SIZE: f32 = 96.;
+const BOSS_SPAWN_DISTANCE: f32 = 900.;
+const BOSS_CHASE_SPEED: f32 = 120.;
+const BOSS_CHASE_TIME: f32 = 2.5; // seconds of chasing between attacks
+const BOSS_WIND_UP_TIME: f32 = 0.8; // the boss stops and aims before charging
+const BOSS_CHARGE_SPEED: f32 = 900.;
+const BOSS_CHARGE_TIME: f32 = 0.7;
+const BOSS_MINION_COUNT: usize = 6;
+const BOSS_MINION_RING: f32 = 120.; // distance from the boss minions spawn at
+const BOSS_BURST_COUNT: usize = 16;
+const BOSS_BURST_SPEED: f32 = 700.;
+const BOSS_BURST_STAGGER_TIME: f32 = 0.6; // burst enemies fly outwards for this long before chasing
+const BOSS_CONTACT_DAMAGE_MULTIPLIER: i32 = 2;
+const BOSS_HIT_COOLDOWN: f32 = 0.5; // one kill mode contact only counts once
+const BOSS_DEATH_PARTICLE_BURSTS: usize = 5;
+
+const BOSS_BAR_BACKGROUND_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BossAttack {
+    Charge,
+    SpawnMinions,
+    RadialBurst,
+}
+
+impl BossAttack {
+    // the order a boss goes through its attacks, repeating
+    const ROTATION: [BossAttack; 4] = [
+        BossAttack::Charge,
+        BossAttack::SpawnMinions,
+        BossAttack::Charge,
+        BossAttack::RadialBurst,
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BossPhase {
+    Chasing,
+    WindingUp,
+    Charging(Vec2), // the direction locked in while winding up
+}
+
+/// A large enemy that shows up every few ticks and takes several kill mode hits to bring down.
+/// Touching it outside of kill mode hurts more than a normal enemy.
+#[derive(Component)]
+pub struct Boss {
+    phase: BossPhase,
+    phase_time_left: f32,
+    attacks_done: usize, // position in `BossAttack::ROTATION`
+    hit_cooldown_left: f32,
+}
+
+#[derive(Bundle)]
+pub struct BossBundle {
+    #[bundle]
+    sprite_bundle: SpriteBundle,
+    boss: Boss,
+    rigidbody: RigidBody,
+    damping: Damping,
+    collider: Collider,
+    velocity: Velocity,
+    gravity_scale: GravityScale,
+    locked_axes: LockedAxes,
+    hitpoints: Hitpoints,
+    themed_kind: ThemedKind,
+}
+
+impl BossBundle {
+    fn new(spawn_position: Vec2, hitpoints: i32) -> BossBundle {
+        BossBundle {
+            sprite_bundle: SpriteBundle {
+                sprite: Sprite {
+                    color: ENEMY_COLOR,
+                    custom_size: Some(Vec2::ONE),
+                    ..default()
+                },
+                transform: Transform {
+                    translation: spawn_position.extend(60.),
+                    scale: Vec3::new(BOSS_SIZE, BOSS_SIZE, 1.),
+                    ..default()
+                },
+                ..default()
+            },
+            boss: Boss {
+                phase: BossPhase::Chasing,
+                phase_time_left: BOSS_CHASE_TIME,
+                attacks_done: 0,
+                hit_cooldown_left: 0.,
+            },
+            rigidbody: RigidBody::Dynamic,
+            damping: Damping {
+                linear_damping: 4.,
+                angular_damping: 4.,
+            },
+            collider: Collider::cuboid(0.5, 0.5),
+            velocity: Velocity::zero(),
+            gravity_scale: GravityScale(0.),
+            locked_axes: LockedAxes::ROTATION_LOCKED_Z,
+            hitpoints: Hitpoints::new(hitpoints.max(1)),
+            themed_kind: ThemedKind::Enemy,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct BossBar; // every node of the boss health bar, shown while a boss is around
+#[derive(Component)]
+pub struct BossBarFill;
+
+pub fn setup_boss_bar(commands: &mut Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Percent(4.),
+                    left: Val::Percent(25.),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(50.), Val::Percent(2.)),
+                ..default()
+            },
+            color: UiColor(BOSS_BAR_BACKGROUND_COLOR),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(BossBar)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                        ..default()
+                    },
+                    color: UiColor(ENEMY_COLOR),
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(BossBarFill)
+                .insert(BossBar);
+        });
+}
+
+/// Brings in a boss on every milestone tick unless one is still around. It always appears at the same
+/// spot relative to the player it comes for, so network peers agree on it without using `SpawnRng`.
+pub fn spawn_bosses(
+    mut tick_events: EventReader<TickEvent>,
+    game_tick_time: Res<GameTickInfo>,
+    config: Res<GameConfig>,
+    bosses: Query<(), With<Boss>>,
+    players: Query<(&Player, &Transform, &Hitpoints)>,
+    music: Res<AudioChannel<MusicChannel>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    if tick_events.iter().count() == 0 {
+        return;
+    }
+    let interval = config.boss_interval_ticks;
+    if interval == 0 || !game_tick_time.ticks.is_multiple_of(interval) || !bosses.is_empty() {
+        return;
+    }
+
+    let mut living_players: Vec<(&Player, &Transform)> = players
+        .iter()
+        .filter(|(_, _, hitpoints)| !hitpoints.is_dead())
+        .map(|(player, transform, _)| (player, transform))
+        .collect();
+    if living_players.is_empty() {
+        return;
+    }
+    living_players.sort_by_key(|(player, _)| player.id);
+    let milestone = game_tick_time.ticks / interval;
+    let (_, target) = living_players[milestone as usize % living_players.len()];
+
+    // each milestone comes from a different direction
+    let angle = milestone as f32 * std::f32::consts::FRAC_PI_3;
+    let position = target.translation.truncate() + Vec2::from_angle(angle) * BOSS_SPAWN_DISTANCE;
+    commands.spawn_bundle(BossBundle::new(position, config.boss_hitpoints));
+
+    // the sting goes through the music channel at its volume, so turning the music off silences it
+    music.play(asset_server.load(BOSS_STING_ASSET_PATH));
+}
+
+/// Chases the nearest player between attacks, then winds up a charge, calls in minions or flings a
+/// ring of enemies outwards. Runs once per simulation step.
+pub fn handle_boss_ai(
+    mut commands: Commands,
+    game_state: Res<GameStateInfo>,
+    players: Query<(&Transform, &Hitpoints), (With<Player>, Without<Boss>)>,
+    mut bosses: Query<(&mut Boss, &Transform, &mut Velocity)>,
+) {
+    if game_state.game_state != GamePlayState::Playing {
+        return;
+    }
+    let living_players = || {
+        players
+            .iter()
+            .filter(|(_, hitpoints)| !hitpoints.is_dead())
+            .map(|(transform, _)| transform.translation.truncate())
+    };
+
+    for (mut boss, transform, mut velocity) in bosses.iter_mut() {
+        let position = transform.translation.truncate();
+        let target = match players::nearest(position, living_players()) {
+            Some(target) => target,
+            None => return,
+        };
+        let to_target = (target - position).normalize_or_zero();
+
+        boss.hit_cooldown_left = (boss.hit_cooldown_left - TIME_STEP).max(0.);
+        boss.phase_time_left -= TIME_STEP;
+        match boss.phase {
+            BossPhase::Chasing => velocity.linvel = to_target * BOSS_CHASE_SPEED,
+            BossPhase::WindingUp => velocity.linvel = Vec2::ZERO,
+            BossPhase::Charging(direction) => velocity.linvel = direction * BOSS_CHARGE_SPEED,
+        }
+        if boss.phase_time_left > 0. {
+            continue;
+        }
+
+        match boss.phase {
+            BossPhase::Chasing => {
+                let attack = BossAttack::ROTATION[boss.attacks_done % BossAttack::ROTATION.len()];
+                boss.attacks_done += 1;
+                match attack {
+                    BossAttack::Charge => {
+                        boss.phase = BossPhase::WindingUp;
+                        boss.phase_time_left = BOSS_WIND_UP_TIME;
+                    }
+                    BossAttack::SpawnMinions => {
+                        spawn_ring(&mut commands, position, BOSS_MINION_COUNT, 0.);
+                        boss.phase_time_left = BOSS_CHASE_TIME;
+                    }
+                    BossAttack::RadialBurst => {
+                        spawn_ring(&mut commands, position, BOSS_BURST_COUNT, BOSS_BURST_SPEED);
+                        boss.phase_time_left = BOSS_CHASE_TIME;
+                    }
+                }
+            }
+            BossPhase::WindingUp => {
+                boss.phase = BossPhase::Charging(to_target);
+                boss.phase_time_left = BOSS_CHARGE_TIME;
+            }
+            BossPhase::Charging(_) => {
+                boss.phase = BossPhase::Chasing;
+                boss.phase_time_left = BOSS_CHASE_TIME;
+            }
+        }
+    }
+}
+
+/// Spawns `count` enemies evenly around `center`, flung outwards at `speed` before they start chasing.
+fn spawn_ring(commands: &mut Commands, center: Vec2, count: usize, speed: f32) {
+    for index in 0..count {
+        let direction = Vec2::from_angle(index as f32 / count as f32 * std::f32::consts::TAU);
+        let mut enemy =
+            commands.spawn_bundle(EnemyBundle::new(center + direction * BOSS_MINION_RING));
+        if speed > 0. {
+            enemy
+                .insert(Velocity::linear(direction * speed))
+                .insert(Staggered {
+                    time_left: BOSS_BURST_STAGGER_TIME,
+                });
+        }
+    }
+}
+
+/// Players in kill mode chip away at a boss they touch, anyone else gets hurt and knocked back.
+pub fn handle_boss_contact(
+    frame_controls: Res<FrameControls>,
+    game_state: Res<GameStateInfo>,
+    config: Res<GameConfig>,
+    settings: Res<GameSettings>,
+    mut players: Query<
+        (
+            Entity,
+            &CollidingEntities,
+            &Transform,
+            &ReadMassProperties,
+            &mut PlayerStats,
+        ),
+        Without<Boss>,
+    >,
+    mut bosses: Query<(&mut Boss, &Transform)>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut popup_events: EventWriter<PopupEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+    mut commands: Commands,
+    mut audio: Res<Audio>,
+    mut asset_server: ResMut<AssetServer>,
+) {
+    // contact lasts as long as the two touch, only count it when the simulation moves on
+    if !frame_controls.simulate || game_state.game_state != GamePlayState::Playing {
+        return;
+    }
+    let palette = settings.palette();
+    for (player_entity, colliding_entities, player_transform, player_mass, mut player_stats) in
+        players.iter_mut()
+    {
+        for collision in colliding_entities.iter() {
+            let (mut boss, boss_transform) = match bosses.get_mut(collision) {
+                Ok(boss) => boss,
+                Err(_) => continue,
+            };
+            let contact = player_transform.translation.truncate();
+            let away = (player_transform.translation - boss_transform.translation)
+                .truncate()
+                .normalize_or_zero();
+
+            if player_stats.kill_mode {
+                if boss.hit_cooldown_left > 0. {
+                    continue;
+                }
+                boss.hit_cooldown_left = BOSS_HIT_COOLDOWN;
+                damage_events.send(DamageEvent {
+                    target: collision,
+                    amount: 1,
+                    source: DamageSource::Contact(player_entity),
+                });
+                particle_events.send(ParticleEvent {
+                    effect: ParticleEffect::EnemyDeath,
+                    position: contact,
+                    direction: -away,
+                });
+                play_sound(
+                    &mut asset_server,
+                    &mut audio,
+                    &settings,
+                    AudioType::EnemyCollisionKillMode,
+                );
+            } else if player_stats.is_invulnerable() {
+                continue;
+            } else if player_stats.powerups.absorb_hit() {
+                popup_events.send(PopupEvent {
+                    position: contact,
+                    text: String::from("BLOCKED"),
+                    color: palette.powerup,
+                });
+                play_sound(
+                    &mut asset_server,
+                    &mut audio,
+                    &settings,
+                    AudioType::EnemyCollisionKillMode,
+                );
+            } else {
+                let damage = config.enemy_contact_damage * BOSS_CONTACT_DAMAGE_MULTIPLIER;
+                damage_events.send(DamageEvent {
+                    target: player_entity,
+                    amount: damage,
+                    source: DamageSource::Contact(collision),
+                });
+                popup_events.send(PopupEvent {
+                    position: contact,
+                    text: format!("-{}", damage),
+                    color: palette.enemy,
+                });
+                particle_events.send(ParticleEvent {
+                    effect: ParticleEffect::PlayerDamage,
+                    position: contact,
+                    direction: Vec2::ZERO,
+                });
+                play_sound(
+                    &mut asset_server,
+                    &mut audio,
+                    &settings,
+                    AudioType::EnemyCollisionDamage,
+                );
+            }
+
+            // whatever happened the player bounces off, the boss is too big to push through
+            player_stats.invulnerable_time_left = config.hit_invulnerable_time;
+            player_stats.knockback_time_left = config.hit_knockback_time;
+            commands.entity(player_entity).insert(ExternalImpulse {
+                impulse: away * config.hit_knockback_speed * player_mass.0.mass,
+                torque_impulse: 0.,
+            });
+        }
+    }
+}
+
+/// Bosses whose hitpoints ran out are removed. Only a boss killed by a player is worth the big
+/// score reward.
+pub fn handle_boss_deaths(
+    mut death_events: EventReader<DeathEvent>,
+    bosses: Query<&Transform, With<Boss>>,
+    players: Query<Entity, With<Player>>,
+    config: Res<GameConfig>,
+    settings: Res<GameSettings>,
+    mut score: ResMut<Score>,
+    mut popup_events: EventWriter<PopupEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+    mut commands: Commands,
+    mut audio: Res<Audio>,
+    mut asset_server: ResMut<AssetServer>,
+) {
+    for event in death_events.iter() {
+        let transform = match bosses.get(event.entity) {
+            Ok(transform) => transform,
+            Err(_) => continue,
+        };
+        let position = transform.translation.truncate();
+        let killed_by_player = match event.source {
+            DamageSource::Contact(entity) | DamageSource::Projectile(entity) => {
+                players.get(entity).is_ok()
+            }
+            DamageSource::Tick => false,
+        };
+        if killed_by_player {
+            score.score += config.boss_kill_score;
+            popup_events.send(PopupEvent {
+                position,
+                text: format!("+{}", config.boss_kill_score),
+                color: SCORE_COLOR,
+            });
+        }
+        for _ in 0..BOSS_DEATH_PARTICLE_BURSTS {
+            particle_events.send(ParticleEvent {
+                effect: ParticleEffect::EnemyDeath,
+                position,
+                direction: Vec2::ZERO,
+            });
+        }
+        play_sound(&mut asset_server, &mut audio, &settings, AudioType::Death);
+        commands.entity(event.entity).despawn_recursive();
+    }
+}
+
+pub fn update_boss_bar(
+    game_state: Res<GameStateInfo>,
+    settings: Res<GameSettings>,
+    bosses: Query<&Hitpoints, With<Boss>>,
+    mut bar: Query<
+        (
+            &mut Visibility,
+            &mut Style,
+            &mut UiColor,
+            Option<&BossBarFill>,
+        ),
+        With<BossBar>,
+    >,
+) {
+    // with more than one boss around the bar follows the healthiest
+    let hitpoints = bosses
+        .iter()
+        .max_by_key(|hitpoints| hitpoints.current)
+        .filter(|_| game_state.game_state == GamePlayState::Playing);
+    let palette = settings.palette();
+
+    for (mut visibility, mut style, mut color, fill) in bar.iter_mut() {
+        visibility.is_visible = hitpoints.is_some();
+        let hitpoints = match (hitpoints, fill) {
+            (Some(hitpoints), Some(_)) => hitpoints,
+            _ => continue,
+        };
+        let fill = hitpoints.current.max(0) as f32 / hitpoints.max as f32 * 100.;
+        style.size.width = Val::Percent(fill);
+        color.0 = palette.enemy;
+    }
+}
@@ -0,0 +1,336 @@
+use crate::players::MAX_PLAYERS;
+use bevy::prelude::*;
+use std::fs;
+use std::str::FromStr;
+
+const CONFIG_FILE: &str = "game_config.txt";
+
+/// Gameplay tuning read from `game_config.txt` next to the game, one `key = value` per line with `#`
+/// comments. Anything missing or invalid keeps its default so the file only needs the values being
+/// changed.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GameConfig {
+    pub dash_speed: f32, // speed the dash impulse gives the player, in pixels per second
+    pub dash_duration: f32, // seconds thrust is ignored after a dash so it isn't cancelled out
+    pub dash_cooldown: f32, // seconds from one dash until the next is ready
+    pub dash_invulnerable_time: f32, // seconds enemies can't damage the player after a dash
+    pub enemy_contact_damage: i32, // health lost when an enemy touches the player
+    pub projectile_damage: i32, // health lost when an enemy projectile hits the player
+    pub hit_invulnerable_time: f32, // seconds after a hit before enemies can hurt the player again
+    pub hit_knockback_speed: f32, // speed the player is pushed away from whatever hit them
+    pub hit_knockback_time: f32, // seconds thrust is ignored while being knocked back
+    pub momentum_acceleration: f32, // pixels per second squared while thrusting
+    pub momentum_coast_drag: f32, // fraction of speed lost per second while not thrusting
+    pub momentum_grip: f32, // how quickly sideways drift is cancelled, lower drifts more
+    pub momentum_turn_acceleration: f32, // radians per second squared
+    pub momentum_max_turn_speed: f32, // radians per second
+    pub boss_interval_ticks: u32, // a boss shows up every this many ticks, 0 turns bosses off
+    pub boss_hitpoints: i32, // kill mode hits it takes to bring a boss down
+    pub boss_kill_score: f32, // score for killing a boss
+    pub upgrade_interval_ticks: u32, // the run stops for an upgrade every this many ticks, 0 turns upgrades off
+}
+
+impl FromWorld for GameConfig {
+    fn from_world(_world: &mut World) -> Self {
+        GameConfig::load()
+    }
+}
+
+impl GameConfig {
+    /// The tuning the game is balanced around, daily challenge runs always play with it.
+    pub fn defaults() -> GameConfig {
+        GameConfig {
+            dash_speed: 1600.,
+            dash_duration: 0.25,
+            dash_cooldown: 1.5,
+            dash_invulnerable_time: 0.35,
+            enemy_contact_damage: 2,
+            projectile_damage: 1,
+            hit_invulnerable_time: 0.8,
+            hit_knockback_speed: 900.,
+            hit_knockback_time: 0.2,
+            momentum_acceleration: 900.,
+            momentum_coast_drag: 0.6,
+            momentum_grip: 1.5,
+            momentum_turn_acceleration: 25.,
+            momentum_max_turn_speed: 5.,
+            boss_interval_ticks: 30,
+            boss_hitpoints: 8,
+            boss_kill_score: 100.,
+            upgrade_interval_ticks: 6,
+        }
+    }
+
+    pub fn load() -> GameConfig {
+        match fs::read_to_string(CONFIG_FILE) {
+            Ok(contents) => GameConfig::parse(&contents),
+            Err(_) => GameConfig::defaults(),
+        }
+    }
+
+    /// Reads the tuning from the contents of a config file, see `to_config_string`.
+    pub fn parse(contents: &str) -> GameConfig {
+        let mut config = GameConfig::defaults();
+        for (key, value) in config_entries(contents) {
+            // bindings live in the same file and are read by InputBindings
+            if InputBindings::is_binding_key(key) {
+                continue;
+            }
+            let number = match value.parse::<f32>() {
+                Ok(number) => number,
+                Err(_) => {
+                    warn!("Ignoring invalid value for {} in {}", key, CONFIG_FILE);
+                    continue;
+                }
+            };
+            match key {
+                "dash_speed" => config.dash_speed = number,
+                "dash_duration" => config.dash_duration = number,
+                "dash_cooldown" => config.dash_cooldown = number,
+                "dash_invulnerable_time" => config.dash_invulnerable_time = number,
+                "enemy_contact_damage" => set_whole(&mut config.enemy_contact_damage, value, key),
+                "projectile_damage" => set_whole(&mut config.projectile_damage, value, key),
+                "hit_invulnerable_time" => config.hit_invulnerable_time = number,
+                "hit_knockback_speed" => config.hit_knockback_speed = number,
+                "hit_knockback_time" => config.hit_knockback_time = number,
+                "momentum_acceleration" => config.momentum_acceleration = number,
+                "momentum_coast_drag" => config.momentum_coast_drag = number,
+                "momentum_grip" => config.momentum_grip = number,
+                "momentum_turn_acceleration" => config.momentum_turn_acceleration = number,
+                "momentum_max_turn_speed" => config.momentum_max_turn_speed = number,
+                "boss_interval_ticks" => set_whole(&mut config.boss_interval_ticks, value, key),
+                "boss_hitpoints" => set_whole(&mut config.boss_hitpoints, value, key),
+                "boss_kill_score" => config.boss_kill_score = number,
+                "upgrade_interval_ticks" => {
+                    set_whole(&mut config.upgrade_interval_ticks, value, key)
+                }
+                _ => warn!("Unknown setting {} in {}", key, CONFIG_FILE),
+            }
+        }
+        config
+    }
+
+    /// Every value written as config file lines, `parse` reads them back exactly.
+    pub fn to_config_string(&self) -> String {
+        let values = [
+            ("dash_speed", self.dash_speed.to_string()),
+            ("dash_duration", self.dash_duration.to_string()),
+            ("dash_cooldown", self.dash_cooldown.to_string()),
+            (
+                "dash_invulnerable_time",
+                self.dash_invulnerable_time.to_string(),
+            ),
+            (
+                "enemy_contact_damage",
+                self.enemy_contact_damage.to_string(),
+            ),
+            ("projectile_damage", self.projectile_damage.to_string()),
+            (
+                "hit_invulnerable_time",
+                self.hit_invulnerable_time.to_string(),
+            ),
+            ("hit_knockback_speed", self.hit_knockback_speed.to_string()),
+            ("hit_knockback_time", self.hit_knockback_time.to_string()),
+            (
+                "momentum_acceleration",
+                self.momentum_acceleration.to_string(),
+            ),
+            ("momentum_coast_drag", self.momentum_coast_drag.to_string()),
+            ("momentum_grip", self.momentum_grip.to_string()),
+            (
+                "momentum_turn_acceleration",
+                self.momentum_turn_acceleration.to_string(),
+            ),
+            (
+                "momentum_max_turn_speed",
+                self.momentum_max_turn_speed.to_string(),
+            ),
+            ("boss_interval_ticks", self.boss_interval_ticks.to_string()),
+            ("boss_hitpoints", self.boss_hitpoints.to_string()),
+            ("boss_kill_score", self.boss_kill_score.to_string()),
+            (
+                "upgrade_interval_ticks",
+                self.upgrade_interval_ticks.to_string(),
+            ),
+        ];
+        values
+            .iter()
+            .map(|(key, value)| format!("{} = {}\n", key, value))
+            .collect()
+    }
+}
+
+/// Settings counted in whole hitpoints or ticks ignore anything that isn't a whole number, like any
+/// other invalid value.
+fn set_whole<T: FromStr>(setting: &mut T, value: &str, key: &str) {
+    match value.parse::<T>() {
+        Ok(whole) => *setting = whole,
+        Err(_) => warn!("Ignoring invalid value for {} in {}", key, CONFIG_FILE),
+    }
+}
+
+/// Keys one player controls their ship with.
+#[derive(Clone, Copy)]
+pub struct PlayerBindings {
+    pub forward: KeyCode,
+    pub turn_left: KeyCode,
+    pub turn_right: KeyCode,
+    pub dash: KeyCode,
+    pub fire: KeyCode, // only used by the aimed weapon
+}
+
+/// Keys every local player controls their ship with, indexed by `Player::id`. Set in the config file
+/// with lines like `dash = LShift` for the first player and `p2_dash = RControl` for the others.
+pub struct InputBindings {
+    pub players: [PlayerBindings; MAX_PLAYERS],
+}
+
+impl FromWorld for InputBindings {
+    fn from_world(_world: &mut World) -> Self {
+        InputBindings::load()
+    }
+}
+
+impl InputBindings {
+    const KEYS: [&'static str; 5] = ["forward", "turn_left", "turn_right", "dash", "fire"];
+
+    /// Split keyboard by default, anyone past the second player will usually be on a gamepad.
+    fn defaults() -> InputBindings {
+        InputBindings {
+            players: [
+                PlayerBindings {
+                    forward: KeyCode::W,
+                    turn_left: KeyCode::A,
+                    turn_right: KeyCode::D,
+                    dash: KeyCode::Space,
+                    fire: KeyCode::LShift,
+                },
+                PlayerBindings {
+                    forward: KeyCode::Up,
+                    turn_left: KeyCode::Left,
+                    turn_right: KeyCode::Right,
+                    dash: KeyCode::RControl,
+                    fire: KeyCode::RShift,
+                },
+                PlayerBindings {
+                    forward: KeyCode::I,
+                    turn_left: KeyCode::J,
+                    turn_right: KeyCode::L,
+                    dash: KeyCode::K,
+                    fire: KeyCode::U,
+                },
+                PlayerBindings {
+                    forward: KeyCode::T,
+                    turn_left: KeyCode::F,
+                    turn_right: KeyCode::H,
+                    dash: KeyCode::G,
+                    fire: KeyCode::Y,
+                },
+            ],
+        }
+    }
+
+    /// Splits a binding key into the player it is for and the control, `None` if it isn't a binding.
+    fn parse_binding_key(key: &str) -> Option<(usize, &str)> {
+        let (player, control) = match key.strip_prefix('p').and_then(|key| key.split_once('_')) {
+            Some((number, control)) => match number.parse::<usize>() {
+                Ok(number) if (1..=MAX_PLAYERS).contains(&number) => (number - 1, control),
+                _ => return None,
+            },
+            None => (0, key),
+        };
+        if !InputBindings::KEYS.contains(&control) {
+            return None;
+        }
+        Some((player, control))
+    }
+
+    fn is_binding_key(key: &str) -> bool {
+        InputBindings::parse_binding_key(key).is_some()
+    }
+
+    pub fn load() -> InputBindings {
+        let mut bindings = InputBindings::defaults();
+        let contents = match fs::read_to_string(CONFIG_FILE) {
+            Ok(contents) => contents,
+            Err(_) => return bindings,
+        };
+
+        for (key, value) in config_entries(&contents) {
+            let (player, control) = match InputBindings::parse_binding_key(key) {
+                Some(binding) => binding,
+                None => continue,
+            };
+            let key_code = match parse_key_code(value) {
+                Some(key_code) => key_code,
+                None => {
+                    warn!("Unknown key {} for {} in {}", value, key, CONFIG_FILE);
+                    continue;
+                }
+            };
+            let player_bindings = &mut bindings.players[player];
+            match control {
+                "forward" => player_bindings.forward = key_code,
+                "turn_left" => player_bindings.turn_left = key_code,
+                "turn_right" => player_bindings.turn_right = key_code,
+                "dash" => player_bindings.dash = key_code,
+                _ => player_bindings.fire = key_code,
+            }
+        }
+        bindings
+    }
+}
+
+/// `key = value` pairs from the config file, skipping blank lines and `#` comments.
+fn config_entries(contents: &str) -> impl Iterator<Item = (&str, &str)> {
+    contents.lines().filter_map(|line| {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let (key, value) = line.split_once('=')?;
+        Some((key.trim(), value.trim()))
+    })
+}
+
+fn parse_key_code(name: &str) -> Option<KeyCode> {
+    let key_code = match name {
+        "A" => KeyCode::A,
+        "B" => KeyCode::B,
+        "C" => KeyCode::C,
+        "D" => KeyCode::D,
+        "E" => KeyCode::E,
+        "F" => KeyCode::F,
+        "G" => KeyCode::G,
+        "H" => KeyCode::H,
+        "I" => KeyCode::I,
+        "J" => KeyCode::J,
+        "K" => KeyCode::K,
+        "L" => KeyCode::L,
+        "M" => KeyCode::M,
+        "N" => KeyCode::N,
+        "O" => KeyCode::O,
+        "P" => KeyCode::P,
+        "Q" => KeyCode::Q,
+        "R" => KeyCode::R,
+        "S" => KeyCode::S,
+        "T" => KeyCode::T,
+        "U" => KeyCode::U,
+        "V" => KeyCode::V,
+        "W" => KeyCode::W,
+        "X" => KeyCode::X,
+        "Y" => KeyCode::Y,
+        "Z" => KeyCode::Z,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Space" => KeyCode::Space,
+        "Tab" => KeyCode::Tab,
+        "LShift" => KeyCode::LShift,
+        "RShift" => KeyCode::RShift,
+        "LControl" => KeyCode::LControl,
+        "RControl" => KeyCode::RControl,
+        "LAlt" => KeyCode::LAlt,
+        "RAlt" => KeyCode::RAlt,
+        _ => return None,
+    };
+    Some(key_code)
+}
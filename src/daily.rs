@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAILY_RESULTS_FILE: &str = "daily_results.txt";
+const SHOWN_DAYS: usize = 5; // days listed under the high scores
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
+
+/// A calendar day in UTC, so everyone playing the daily on the same day gets the same one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DailyDate {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl DailyDate {
+    pub fn today() -> DailyDate {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+        DailyDate::from_days_since_epoch((seconds / SECONDS_PER_DAY) as i64)
+    }
+
+    /// Converts days since 1970-01-01 to a date in the proleptic Gregorian calendar.
+    fn from_days_since_epoch(days: i64) -> DailyDate {
+        // shift the epoch to 0000-03-01 so leap days fall at the end of each 400 year era
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153; // counting from March
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        } as u32;
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        DailyDate { year, month, day }
+    }
+
+    /// The spawn seed for the day, the date written out as digits like 20240131.
+    pub fn seed(&self) -> u64 {
+        (self.year as u64) * 10_000 + (self.month as u64) * 100 + self.day as u64
+    }
+
+    pub fn label(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Whether the current run is the daily challenge, and for which day. Daily runs use the day's seed
+/// and the default gameplay tuning whatever the config file says, so everyone plays the same run.
+#[derive(Default)]
+pub struct DailyChallenge {
+    pub date: Option<DailyDate>, // None for normal runs
+}
+
+pub struct DailyResult {
+    pub date: String,
+    pub score: f32,
+}
+
+/// Every daily run played on this machine, oldest first, saved to disk one `date score` per line
+/// separately from the high score table.
+pub struct DailyResults {
+    pub entries: Vec<DailyResult>,
+}
+
+impl FromWorld for DailyResults {
+    fn from_world(_world: &mut World) -> Self {
+        DailyResults::load()
+    }
+}
+
+impl DailyResults {
+    pub fn load() -> DailyResults {
+        let entries = match fs::read_to_string(DAILY_RESULTS_FILE) {
+            Ok(contents) => contents
+                .lines()
+                .filter_map(|line| {
+                    let (date, score) = line.trim().split_once(' ')?;
+                    Some(DailyResult {
+                        date: String::from(date),
+                        score: score.trim().parse::<f32>().ok()?,
+                    })
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        DailyResults { entries }
+    }
+
+    pub fn save(&self) {
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|entry| format!("{} {}\n", entry.date, entry.score))
+            .collect();
+        if let Err(error) = fs::write(DAILY_RESULTS_FILE, contents) {
+            warn!("Failed to save daily results: {}", error);
+        }
+    }
+
+    /// Adds the result of a daily run to the history and saves it.
+    pub fn submit(&mut self, date: DailyDate, score: f32) {
+        self.entries.push(DailyResult {
+            date: date.label(),
+            score,
+        });
+        self.save();
+    }
+
+    /// The best score of each of the most recent days that were played, newest first.
+    pub fn recent_bests(&self) -> Vec<(&str, f32)> {
+        let mut bests: Vec<(&str, f32)> = Vec::new();
+        for entry in self.entries.iter().rev() {
+            match bests.iter_mut().find(|(date, _)| *date == entry.date) {
+                Some((_, best)) => *best = best.max(entry.score),
+                None => bests.push((&entry.date, entry.score)),
+            }
+        }
+        bests.sort_by(|a, b| b.0.cmp(a.0));
+        bests.truncate(SHOWN_DAYS);
+        bests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i64, month: u32, day: u32) -> DailyDate {
+        DailyDate { year, month, day }
+    }
+
+    fn results(entries: &[(&str, f32)]) -> DailyResults {
+        DailyResults {
+            entries: entries
+                .iter()
+                .map(|(date, score)| DailyResult {
+                    date: String::from(*date),
+                    score: *score,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn days_since_epoch_become_dates() {
+        assert_eq!(DailyDate::from_days_since_epoch(0), date(1970, 1, 1));
+        assert_eq!(DailyDate::from_days_since_epoch(11_016), date(2000, 2, 29));
+        assert_eq!(DailyDate::from_days_since_epoch(20_088), date(2024, 12, 31));
+        assert_eq!(date(2024, 12, 31).seed(), 20_241_231);
+        assert_eq!(date(2000, 2, 29).label(), "2000-02-29");
+    }
+
+    #[test]
+    fn recent_bests_keep_the_best_of_each_day_newest_first() {
+        let results = results(&[
+            ("2024-01-02", 40.),
+            ("2024-01-01", 10.),
+            ("2024-01-02", 90.),
+            ("2024-01-03", 5.),
+            ("2024-01-02", 60.),
+        ]);
+        assert_eq!(
+            results.recent_bests(),
+            vec![("2024-01-03", 5.), ("2024-01-02", 90.), ("2024-01-01", 10.)]
+        );
+    }
+
+    #[test]
+    fn recent_bests_only_show_the_latest_days() {
+        let results = results(&[
+            ("2024-01-01", 1.),
+            ("2024-01-02", 2.),
+            ("2024-01-03", 3.),
+            ("2024-01-04", 4.),
+            ("2024-01-05", 5.),
+            ("2024-01-06", 6.),
+        ]);
+        let bests = results.recent_bests();
+        assert_eq!(bests.len(), SHOWN_DAYS);
+        assert_eq!(bests.first(), Some(&("2024-01-06", 6.)));
+        assert_eq!(bests.last(), Some(&("2024-01-02", 2.)));
+    }
+}
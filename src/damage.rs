@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+
+/// Health of anything that can be hurt, the player and every enemy.
+#[derive(Component, Clone, Copy)]
+pub struct Hitpoints {
+    pub current: i32,
+    pub max: i32,
+    pub overheal_max: i32, // healing can go past max up to this, equal to max when there's no overheal
+}
+
+impl Hitpoints {
+    pub fn new(max: i32) -> Hitpoints {
+        Hitpoints {
+            current: max,
+            max,
+            overheal_max: max,
+        }
+    }
+
+    pub fn with_overheal(max: i32, overheal_max: i32) -> Hitpoints {
+        Hitpoints {
+            current: max,
+            max,
+            overheal_max,
+        }
+    }
+
+    pub fn heal(&mut self, amount: i32) {
+        self.current = (self.current + amount).min(self.overheal_max);
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0
+    }
+}
+
+/// What caused some damage, so deaths can be credited.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DamageSource {
+    Tick,               // the player's health draining every game tick
+    Contact(Entity),    // touching another unit
+    Projectile(Entity), // shot by a player, the shooter
+}
+
+/// Send to hurt anything with `Hitpoints`. Damage to entities that are gone or already dead is ignored.
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: i32,
+    pub source: DamageSource,
+}
+
+/// Sent once when something's hitpoints run out. Whoever owns that kind of unit decides what dying
+/// means for it.
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub source: DamageSource,
+}
+
+pub fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut hitpoints: Query<&mut Hitpoints>,
+) {
+    for event in damage_events.iter() {
+        let mut target_hitpoints = match hitpoints.get_mut(event.target) {
+            Ok(target_hitpoints) => target_hitpoints,
+            Err(_) => continue,
+        };
+        if target_hitpoints.is_dead() {
+            continue;
+        }
+
+        target_hitpoints.current -= event.amount;
+        if target_hitpoints.is_dead() {
+            death_events.send(DeathEvent {
+                entity: event.target,
+                source: event.source,
+            });
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::config::GameConfig;
+use crate::definitions_units::{Player, PlayerStats};
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::particles::{ParticleEffect, ParticleEvent};
+use crate::players::FrameControls;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+const DASH_PARTICLE_BURSTS: usize = 12;
+
+/// Dashes each player the way they are facing with a rapier impulse when their dash binding (or their
+/// gamepad's south button) is pressed and the cooldown is up. Every step uses up the dash presses,
+/// whether they lead to a dash or not.
+pub fn player_dash(
+    mut commands: Commands,
+    mut frame_controls: ResMut<FrameControls>,
+    config: Res<GameConfig>,
+    game_state: Res<GameStateInfo>,
+    mut players: Query<(
+        Entity,
+        &Player,
+        &mut PlayerStats,
+        &Transform,
+        &ReadMassProperties,
+    )>,
+    mut particle_events: EventWriter<ParticleEvent>,
+) {
+    let dashes = frame_controls.players.map(|controls| controls.dash);
+    for controls in frame_controls.players.iter_mut() {
+        controls.dash = false;
+    }
+    if game_state.game_state != GamePlayState::Playing || !frame_controls.simulate {
+        return;
+    }
+
+    for (entity, player, mut player_stats, transform, mass_properties) in players.iter_mut() {
+        if !dashes[player.id] || player_stats.dash_cooldown_left > 0. {
+            continue;
+        }
+
+        let facing = (transform.rotation * Vec3::Y).truncate();
+        commands.entity(entity).insert(ExternalImpulse {
+            impulse: facing * config.dash_speed * mass_properties.0.mass,
+            torque_impulse: 0.,
+        });
+
+        player_stats.current_speed = Vec3::ZERO;
+        player_stats.dash_time_left = config.dash_duration;
+        player_stats.dash_cooldown_left = config.dash_cooldown;
+        player_stats.invulnerable_time_left = config.dash_invulnerable_time;
+
+        for _ in 0..DASH_PARTICLE_BURSTS {
+            particle_events.send(ParticleEvent {
+                effect: ParticleEffect::Thrust,
+                position: transform.translation.truncate(),
+                direction: -facing,
+            });
+        }
+    }
+}
@@ -1,4 +1,8 @@
-﻿use crate::HealthGone;
+﻿use crate::damage::Hitpoints;
+use crate::powerups::{ActivePowerups, PowerupKind};
+use crate::theme::ThemedKind;
+use crate::weapon::Weapon;
+use crate::PlayerInput;
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
@@ -9,10 +13,10 @@ pub const PLAYER_COLOR: Color = Color::Rgba {
     alpha: 1.0,
 };
 
-const HEALTH_COLOR: Color = Color::Rgba {
-    red: 0.0,
-    green: 0.4,
-    blue: 1.0,
+pub const HEALTH_COLOR: Color = Color::Rgba {
+    red: 0.2,
+    green: 0.85,
+    blue: 0.35,
     alpha: 1.0,
 };
 
@@ -29,65 +33,107 @@ pub const ENEMY_COLOR: Color = Color::Rgba {
     alpha: 1.0,
 };
 
+pub const MAX_KILL_MODE_TIME: f32 = 5.;
+pub const MAX_HEALTH: i32 = 10;
+pub const MAX_OVERHEAL_HEALTH: i32 = 15; // only pickups can heal past MAX_HEALTH, the tick drains it back
+pub const HEALTH_PICKUP_HEAL: i32 = 3;
+pub const ENEMY_HITPOINTS: i32 = 1;
+pub const RANGED_ENEMY_MIN_DISTANCE: f32 = 350.; // backs off from players closer than this
+pub const RANGED_ENEMY_MAX_DISTANCE: f32 = 550.; // closes in on players further than this
+pub const RANGED_ENEMY_FIRE_INTERVAL: f32 = 2.;
+pub const RANGED_ENEMY_PROJECTILE_SPEED: f32 = 450.;
+const RANGED_ENEMY_SIZE: f32 = 24.; // smaller than chasers so they can be told apart
+
+/// Everything about one player that isn't physics, on the player entity next to `Player`.
+#[derive(Component)]
 pub struct PlayerStats {
     pub speed_per_frame: f32,
     pub max_speed: f32,
     pub current_speed: Vec3,
-    pub health: i32,
     pub kill_mode: bool,
     pub time_left_in_kill_mode: f32,
+    pub kill_mode_bonus_time: f32, // from upgrades, added to each kill mode pickup and its cap
+    pub pickup_magnet_radius: f32, // pickups closer than this get pulled to the player
+    pub powerups: ActivePowerups,
+    pub weapon: Weapon,
+    pub dash_time_left: f32,
+    pub dash_cooldown_left: f32,
+    pub invulnerable_time_left: f32,
+    pub knockback_time_left: f32,
 }
 
 impl PlayerStats {
     pub fn add_forward_speed(&mut self, added_speed: f32) {
         self.current_speed.y += added_speed;
-        if self.current_speed.y > self.max_speed {
-            self.current_speed.y = self.max_speed;
+        let max_speed = self.max_speed * self.powerups.speed_multiplier();
+        if self.current_speed.y > max_speed {
+            self.current_speed.y = max_speed;
         }
     }
 
-    pub fn health_damage(
-        &mut self,
-        amount_to_remove: i32,
-        mut health_event: &mut EventWriter<HealthGone>,
-    ) {
-        self.health -= amount_to_remove;
-        if self.health <= 0 {
-            health_event.send(HealthGone {})
+    pub fn activate_powerup(&mut self, kind: PowerupKind) {
+        match kind {
+            PowerupKind::KillMode => self.powerup_mode(),
+            PowerupKind::WeaponUpgrade => self.weapon.upgrade(),
+            _ => self.powerups.activate(kind),
         }
     }
 
-    pub fn health_heal(&mut self, amount_to_add: i32) {
-        self.health += amount_to_add;
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable_time_left > 0.
+    }
+
+    /// Thrust leaves the velocity alone while a dash or knockback plays out so it isn't cancelled.
+    pub fn is_thrust_locked(&self) -> bool {
+        self.dash_time_left > 0. || self.knockback_time_left > 0.
     }
 
-    pub fn health_heal_up_to_ten(&mut self) {
-        self.health = 10;
+    /// Counts the dash, invulnerability and knockback timers down by `delta` seconds.
+    pub fn status_time_decrease(&mut self, delta: f32) {
+        self.dash_time_left = (self.dash_time_left - delta).max(0.);
+        self.dash_cooldown_left = (self.dash_cooldown_left - delta).max(0.);
+        self.invulnerable_time_left = (self.invulnerable_time_left - delta).max(0.);
+        self.knockback_time_left = (self.knockback_time_left - delta).max(0.);
     }
 
-    pub fn powerup_mode(&mut self, mut player_sprite: &mut Mut<Sprite>) {
-        player_sprite.color = POWERUP_COLOR;
+    pub fn magnet_radius(&self) -> f32 {
+        self.pickup_magnet_radius * self.powerups.magnet_multiplier()
+    }
+
+    pub fn powerup_mode(&mut self) {
         self.kill_mode = true;
-        self.time_left_in_kill_mode += 3.;
-        if self.time_left_in_kill_mode > 5.{
-            self.time_left_in_kill_mode = 5.;
+        self.time_left_in_kill_mode += 3. + self.kill_mode_bonus_time;
+        if self.time_left_in_kill_mode > self.max_kill_mode_time() {
+            self.time_left_in_kill_mode = self.max_kill_mode_time();
         }
     }
-    pub fn powerup_time_decrease(&mut self, mut player_sprite: &mut Mut<Sprite>) {
-        if self.time_left_in_kill_mode > 0.{
-            self.time_left_in_kill_mode -= 1.;
 
-        }
-        info!(self.time_left_in_kill_mode);
+    pub fn max_kill_mode_time(&self) -> f32 {
+        MAX_KILL_MODE_TIME + self.kill_mode_bonus_time
+    }
+    /// Counts every running powerup down by `delta` seconds.
+    pub fn powerup_time_decrease(&mut self, delta: f32) {
+        self.time_left_in_kill_mode = (self.time_left_in_kill_mode - delta).max(0.);
         if self.time_left_in_kill_mode <= 0. {
-            player_sprite.color = PLAYER_COLOR;
             self.kill_mode = false;
         }
+        self.powerups.time_decrease(delta);
+    }
+
+    /// Seconds left on a timed powerup, `None` for kinds that aren't timed.
+    pub fn powerup_time_left(&self, kind: PowerupKind) -> Option<f32> {
+        match kind {
+            PowerupKind::KillMode => Some(self.time_left_in_kill_mode),
+            PowerupKind::SpeedBoost => Some(self.powerups.speed_boost_time_left),
+            PowerupKind::TimeSlow => Some(self.powerups.time_slow_time_left),
+            PowerupKind::Magnet => Some(self.powerups.magnet_time_left),
+            PowerupKind::Shield | PowerupKind::Shockwave | PowerupKind::WeaponUpgrade => None,
+        }
     }
 }
 
-impl FromWorld for PlayerStats {
-    fn from_world(world: &mut World) -> Self {
+impl Default for PlayerStats {
+    fn default() -> PlayerStats {
         PlayerStats {
             speed_per_frame: 10.,
             max_speed: 600.,
@@ -96,15 +142,24 @@ impl FromWorld for PlayerStats {
                 y: 0.0,
                 z: 0.0,
             },
-            health: 10,
             kill_mode: false,
             time_left_in_kill_mode: 0.0,
+            kill_mode_bonus_time: 0.,
+            pickup_magnet_radius: 160.,
+            powerups: ActivePowerups::default(),
+            weapon: Weapon::default(),
+            dash_time_left: 0.,
+            dash_cooldown_left: 0.,
+            invulnerable_time_left: 0.,
+            knockback_time_left: 0.,
         }
     }
 }
 
 #[derive(Component)]
-pub struct Player;
+pub struct Player {
+    pub id: usize, // which local player this is, picks their bindings, gamepad and hud
+}
 
 #[derive(Component)]
 pub struct PlayerCam;
@@ -117,28 +172,30 @@ pub struct PlayerBundle {
     damping: Damping,
     collider: Collider,
     velocity: Velocity,
+    mass_properties: ReadMassProperties,
     ccd: Ccd,
     player: Player,
     gravity_scale: GravityScale,
     locked_axes: LockedAxes,
     active_events: ActiveEvents,
     colliding_entities: CollidingEntities,
+    hitpoints: Hitpoints,
+    stats: PlayerStats,
+    input: PlayerInput,
+    themed_kind: ThemedKind,
 }
 
 impl PlayerBundle {
-    pub(crate) fn new() -> PlayerBundle {
+    pub(crate) fn new(id: usize, spawn_position: Vec2) -> PlayerBundle {
         PlayerBundle {
             sprite_bundle: SpriteBundle {
                 sprite: Sprite {
                     color: PLAYER_COLOR,
+                    custom_size: Some(Vec2::ONE),
                     ..default()
                 },
                 transform: Transform {
-                    translation: Vec3 {
-                        x: 0.0,
-                        y: 0.0,
-                        z: 100.0,
-                    },
+                    translation: spawn_position.extend(100.),
                     rotation: Default::default(),
                     scale: Vec3 {
                         x: 16.0,
@@ -158,18 +215,23 @@ impl PlayerBundle {
                 linvel: Vec2::new(0., 0.),
                 angvel: 0.,
             },
+            mass_properties: ReadMassProperties::default(),
             ccd: Ccd { enabled: false },
-            player: Player,
+            player: Player { id },
             gravity_scale: GravityScale(0.),
             locked_axes: LockedAxes::ROTATION_LOCKED_Z,
             active_events: ActiveEvents::COLLISION_EVENTS,
             colliding_entities: Default::default(),
+            hitpoints: Hitpoints::with_overheal(MAX_HEALTH, MAX_OVERHEAL_HEALTH),
+            stats: PlayerStats::default(),
+            input: PlayerInput::default(),
+            themed_kind: ThemedKind::Player,
         }
     }
 }
 
 #[derive(Component)]
-pub struct Powerup;
+pub struct Powerup(pub PowerupKind);
 
 #[derive(Bundle)]
 pub struct PowerupBundle {
@@ -179,15 +241,18 @@ pub struct PowerupBundle {
     collider: Collider,
     sensor: Sensor,
     gravity_scale: GravityScale,
+    velocity: Velocity,
     powerup: Powerup,
+    themed_kind: ThemedKind,
 }
 
 impl PowerupBundle {
-    pub(crate) fn new(spawn_position: Vec2) -> PowerupBundle {
+    pub(crate) fn new(spawn_position: Vec2, kind: PowerupKind) -> PowerupBundle {
         PowerupBundle {
             sprite_bundle: SpriteBundle {
                 sprite: Sprite {
                     color: POWERUP_COLOR,
+                    custom_size: Some(Vec2::ONE),
                     ..default()
                 },
                 transform: Transform {
@@ -205,7 +270,9 @@ impl PowerupBundle {
             collider: Collider::cuboid(0.5, 0.5),
             sensor: Sensor,
             gravity_scale: GravityScale(0.),
-            powerup: Powerup,
+            velocity: Velocity::zero(),
+            powerup: Powerup(kind),
+            themed_kind: ThemedKind::Powerup,
         }
     }
 }
@@ -221,7 +288,9 @@ pub struct HealthBundle {
     collider: Collider,
     sensor: Sensor,
     gravity_scale: GravityScale,
+    velocity: Velocity,
     health: Health,
+    themed_kind: ThemedKind,
 }
 
 impl HealthBundle {
@@ -230,6 +299,7 @@ impl HealthBundle {
             sprite_bundle: SpriteBundle {
                 sprite: Sprite {
                     color: HEALTH_COLOR,
+                    custom_size: Some(Vec2::ONE),
                     ..default()
                 },
                 transform: Transform {
@@ -247,7 +317,9 @@ impl HealthBundle {
             collider: Collider::cuboid(0.5, 0.5),
             sensor: Sensor,
             gravity_scale: GravityScale(0.),
+            velocity: Velocity::zero(),
             health: Health,
+            themed_kind: ThemedKind::Health,
         }
     }
 }
@@ -264,8 +336,11 @@ pub struct EnemyBundle {
     damping: Damping,
     collider: Collider,
     velocity: Velocity,
+    mass_properties: ReadMassProperties,
     ccd: Ccd,
     gravity_scale: GravityScale,
+    hitpoints: Hitpoints,
+    themed_kind: ThemedKind,
     //restitution: Restitution,
 }
 
@@ -275,6 +350,7 @@ impl EnemyBundle {
             sprite_bundle: SpriteBundle {
                 sprite: Sprite {
                     color: ENEMY_COLOR,
+                    custom_size: Some(Vec2::ONE),
                     ..default()
                 },
                 transform: Transform {
@@ -299,9 +375,38 @@ impl EnemyBundle {
                 linvel: Vec2::new(0., 0.),
                 angvel: 0.,
             },
+            mass_properties: ReadMassProperties::default(),
             ccd: Ccd { enabled: false },
             gravity_scale: GravityScale(0.),
+            hitpoints: Hitpoints::new(ENEMY_HITPOINTS),
+            themed_kind: ThemedKind::Enemy,
             //restitution: Restitution { coefficient: 5., combine_rule: CoefficientCombineRule::Max }
         }
     }
 }
+
+/// Keeps its distance from the players and shoots at them instead of chasing.
+#[derive(Component)]
+pub struct RangedEnemy {
+    pub fire_cooldown_left: f32,
+}
+
+#[derive(Bundle)]
+pub struct RangedEnemyBundle {
+    #[bundle]
+    enemy: EnemyBundle,
+    ranged: RangedEnemy,
+}
+
+impl RangedEnemyBundle {
+    pub(crate) fn new(spawn_position: Vec2) -> RangedEnemyBundle {
+        let mut enemy = EnemyBundle::new(spawn_position);
+        enemy.sprite_bundle.transform.scale = Vec3::new(RANGED_ENEMY_SIZE, RANGED_ENEMY_SIZE, 1.);
+        RangedEnemyBundle {
+            enemy,
+            ranged: RangedEnemy {
+                fire_cooldown_left: RANGED_ENEMY_FIRE_INTERVAL,
+            },
+        }
+    }
+}
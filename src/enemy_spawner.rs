@@ -1,32 +1,63 @@
 ﻿use crate::definitions_units::*;
+use crate::powerups::{choose_weighted, PowerupKind};
 use crate::{definitions_units, GameTickInfo};
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
+use rand::rngs::StdRng;
 use rand::*;
 
 const SCREEN_SAFE_WIDTH: f32 = 2050.;
 const SCREEN_SAFE_HEIGHT: f32 = 1150.;
 const SPAWN_WIDTH: f32 = 50.;
+const RANGED_ENEMY_ODDS: u32 = 12; // one in this many enemies of a wave is ranged
 
 #[derive(Default)]
 pub struct SpawnEvents(pub bool);
 
+/// Random numbers for everything spawned during a run. Reseeded when a run starts so the same seed
+/// always spawns the same run, which is what lets networked peers simulate it in step.
+pub struct SpawnRng {
+    pub seed: u64,
+    rng: StdRng,
+}
+
+impl FromWorld for SpawnRng {
+    fn from_world(_world: &mut World) -> Self {
+        let seed = thread_rng().gen();
+        SpawnRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl SpawnRng {
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+}
+
 pub struct Spawner {
     amount_to_spawn_next_tick: u32,
+    pub powerup_weights: Vec<(PowerupKind, u32)>, // relative chance of each powerup kind
 }
 
 impl Spawner {
     pub fn spawn_next_wave(
-        mut player_transform: &mut Query<&Transform, (With<Player>, Without<Enemy>)>,
+        player_transform: &Transform,
 
         game_tick_time: &ResMut<GameTickInfo>,
         mut enemy_spawner: &ResMut<Spawner>,
+        spawn_rng: &mut SpawnRng,
         mut commands: &mut Commands,
     ) {
-        let (player_transform) = player_transform.single_mut();
-
-        let mut rng = thread_rng();
+        let rng = spawn_rng.rng();
 
         let mut x_position: f32 = 0.;
         let mut y_position: f32 = 0.;
@@ -68,22 +99,26 @@ impl Spawner {
                         rng.gen_range((playery - 1080. - 100.)..(playery + SCREEN_SAFE_HEIGHT));
                 }
             }
-            commands.spawn_bundle(EnemyBundle::new(Vec2 {
+            let position = Vec2 {
                 x: x_position as f32,
                 y: y_position as f32,
-            }));
+            };
+            if rng.gen_ratio(1, RANGED_ENEMY_ODDS) {
+                commands.spawn_bundle(RangedEnemyBundle::new(position));
+            } else {
+                commands.spawn_bundle(EnemyBundle::new(position));
+            }
         }
     }
 
     pub fn spawn_health(
-        mut player_transform: &mut Query<&Transform, (With<Player>, Without<Enemy>)>,
+        player_transform: &Transform,
 
         mut enemy_spawner: &ResMut<Spawner>,
+        spawn_rng: &mut SpawnRng,
         mut commands: &mut Commands,
     ) {
-        let (player_transform) = player_transform.single_mut();
-
-        let mut rng = thread_rng();
+        let rng = spawn_rng.rng();
 
         let mut x_position: f32 = 0.;
         let mut y_position: f32 = 0.;
@@ -133,14 +168,13 @@ impl Spawner {
     }
 
     pub fn spawn_powerup(
-        mut player_transform: &mut Query<&Transform, (With<Player>, Without<Enemy>)>,
+        player_transform: &Transform,
 
         mut enemy_spawner: &ResMut<Spawner>,
+        spawn_rng: &mut SpawnRng,
         mut commands: &mut Commands,
     ) {
-        let (player_transform) = player_transform.single_mut();
-
-        let mut rng = thread_rng();
+        let rng = spawn_rng.rng();
 
         let mut x_position: f32 = 0.;
         let mut y_position: f32 = 0.;
@@ -184,10 +218,14 @@ impl Spawner {
                         rng.gen_range((playery - 1080. - 100.)..(playery + SCREEN_SAFE_HEIGHT));
                 }
             }
-            commands.spawn_bundle(PowerupBundle::new(Vec2 {
-                x: x_position as f32,
-                y: y_position as f32,
-            }));
+            let kind = choose_weighted(&enemy_spawner.powerup_weights, rng);
+            commands.spawn_bundle(PowerupBundle::new(
+                Vec2 {
+                    x: x_position as f32,
+                    y: y_position as f32,
+                },
+                kind,
+            ));
         }
     }
 }
@@ -196,6 +234,10 @@ impl FromWorld for Spawner {
     fn from_world(world: &mut World) -> Self {
         Spawner {
             amount_to_spawn_next_tick: 35,
+            powerup_weights: PowerupKind::ALL
+                .iter()
+                .map(|kind| (*kind, kind.default_spawn_weight()))
+                .collect(),
         }
     }
 }
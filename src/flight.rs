@@ -0,0 +1,94 @@
+use crate::config::GameConfig;
+use crate::daily::DailyChallenge;
+use crate::definitions_units::PlayerStats;
+use crate::settings::GameSettings;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+const ARCADE_DAMPING: f32 = 4.; // matches the damping the player bundle spawns with
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FlightModel {
+    Arcade,   // the original controls, full speed while thrusting and a hard stop on release
+    Momentum, // thrust accelerates, the ship coasts and drifts and turning ramps up and down
+}
+
+impl FlightModel {
+    pub const ALL: [FlightModel; 2] = [FlightModel::Arcade, FlightModel::Momentum];
+
+    /// The flight model of the run being played. Daily challenges fly the original arcade controls
+    /// whatever the setting says, like the rest of their rules.
+    pub fn current(settings: &GameSettings, daily: &DailyChallenge) -> FlightModel {
+        if daily.date.is_some() {
+            FlightModel::Arcade
+        } else {
+            settings.flight_model
+        }
+    }
+
+    pub fn next(&self) -> FlightModel {
+        match self {
+            FlightModel::Arcade => FlightModel::Momentum,
+            FlightModel::Momentum => FlightModel::Arcade,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FlightModel::Arcade => "Arcade",
+            FlightModel::Momentum => "Momentum",
+        }
+    }
+
+    /// The momentum model does its own drag so rapier damping is turned off for it.
+    pub fn damping(&self) -> Damping {
+        match self {
+            FlightModel::Arcade => Damping {
+                linear_damping: ARCADE_DAMPING,
+                angular_damping: ARCADE_DAMPING,
+            },
+            FlightModel::Momentum => Damping {
+                linear_damping: 0.,
+                angular_damping: 0.,
+            },
+        }
+    }
+}
+
+/// One frame of the momentum flight model. `turn` is 1 for left, -1 for right and 0 for neither.
+pub fn fly_momentum(
+    thrusting: bool,
+    turn: f32,
+    delta: f32,
+    player_stats: &PlayerStats,
+    config: &GameConfig,
+    transform: &Transform,
+    velocity: &mut Velocity,
+) {
+    // turn inertia, the turn rate eases towards what the player is holding
+    let target_angvel = turn * config.momentum_max_turn_speed;
+    let max_angvel_change = config.momentum_turn_acceleration * delta;
+    velocity.angvel +=
+        (target_angvel - velocity.angvel).clamp(-max_angvel_change, max_angvel_change);
+
+    // dashes and knockback are left alone so the top speed doesn't clip them
+    if player_stats.is_thrust_locked() {
+        return;
+    }
+
+    let facing = (transform.rotation * Vec3::Y).truncate();
+    let mut linvel = velocity.linvel;
+    if thrusting {
+        linvel += facing * config.momentum_acceleration * delta;
+    } else {
+        linvel *= (1. - config.momentum_coast_drag * delta).max(0.);
+    }
+
+    // drift, sideways velocity bleeds off slower the lower the grip
+    let forward = facing * linvel.dot(facing);
+    let sideways = linvel - forward;
+    linvel = forward + sideways * (1. - config.momentum_grip * delta).max(0.);
+
+    let max_speed = player_stats.max_speed * player_stats.powerups.speed_multiplier();
+    velocity.linvel = linvel.clamp_length_max(max_speed);
+}
@@ -0,0 +1,142 @@
+use crate::daily::DailyChallenge;
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::settings::GameSettings;
+use crate::{GameTickInfo, PlayingText, FONT_ASSET_PATH, HEALTH_FONT_SIZE, TEXT_COLOR};
+use bevy::prelude::*;
+
+const TIME_ATTACK_SECONDS: f32 = 180.;
+const TARGET_TICKS: u32 = 120;
+const GOAL_TEXT_TOP: Val = Val::Px(55.); // the line under the score
+const GOAL_TEXT_LEFT: Val = Val::Px(5.);
+
+/// What a run is played for. Every mode is lost when the last player dies, the modes other than
+/// endless can also be won.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameMode {
+    Endless,    // survive as long as possible
+    TimeAttack, // score as much as possible before the time runs out
+    Target,     // survive a set number of ticks
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 3] = [GameMode::Endless, GameMode::TimeAttack, GameMode::Target];
+
+    /// The mode of the run being played. Daily challenges are always endless so their results
+    /// compare with each other.
+    pub fn current(settings: &GameSettings, daily: &DailyChallenge) -> GameMode {
+        if daily.date.is_some() {
+            GameMode::Endless
+        } else {
+            settings.game_mode
+        }
+    }
+
+    pub fn next(&self) -> GameMode {
+        match self {
+            GameMode::Endless => GameMode::TimeAttack,
+            GameMode::TimeAttack => GameMode::Target,
+            GameMode::Target => GameMode::Endless,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            GameMode::Endless => 0,
+            GameMode::TimeAttack => 1,
+            GameMode::Target => 2,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Endless => "Endless",
+            GameMode::TimeAttack => "Time Attack",
+            GameMode::Target => "Target",
+        }
+    }
+
+    /// Whether the run has been won, checked every frame while playing.
+    pub fn is_won(&self, game_tick_time: &GameTickInfo) -> bool {
+        match self {
+            GameMode::Endless => false,
+            GameMode::TimeAttack => game_tick_time.run_time >= TIME_ATTACK_SECONDS,
+            GameMode::Target => game_tick_time.ticks >= TARGET_TICKS,
+        }
+    }
+
+    /// Shown above the final score on the win screen.
+    pub fn win_text(&self) -> &'static str {
+        match self {
+            GameMode::Endless => "FINAL SCORE:",
+            GameMode::TimeAttack => "TIME UP! SCORE:",
+            GameMode::Target => "TARGET SURVIVED! SCORE:",
+        }
+    }
+
+    /// How far along the goal of the mode the run is, `None` for endless.
+    fn goal_text(&self, game_tick_time: &GameTickInfo) -> Option<String> {
+        match self {
+            GameMode::Endless => None,
+            GameMode::TimeAttack => {
+                let seconds_left = (TIME_ATTACK_SECONDS - game_tick_time.run_time)
+                    .max(0.)
+                    .ceil();
+                Some(format!(
+                    "TIME: {}:{:02}",
+                    seconds_left as u32 / 60,
+                    seconds_left as u32 % 60
+                ))
+            }
+            GameMode::Target => Some(format!(
+                "SURVIVE: {}/{}",
+                game_tick_time.ticks.min(TARGET_TICKS),
+                TARGET_TICKS
+            )),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ModeGoalText;
+
+pub fn setup_mode_goal_text(commands: &mut Commands, asset_server: &mut ResMut<AssetServer>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT_ASSET_PATH),
+                    font_size: HEALTH_FONT_SIZE,
+                    color: TEXT_COLOR,
+                },
+            )
+            .with_text_alignment(TextAlignment::TOP_LEFT)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: GOAL_TEXT_TOP,
+                    left: GOAL_TEXT_LEFT,
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(ModeGoalText)
+        .insert(PlayingText);
+}
+
+/// Counts down the time attack clock or up to the target, empty in endless runs.
+pub fn update_mode_goal_text(
+    game_state: Res<GameStateInfo>,
+    game_tick_time: Res<GameTickInfo>,
+    settings: Res<GameSettings>,
+    daily: Res<DailyChallenge>,
+    mut goal_text: Query<&mut Text, With<ModeGoalText>>,
+) {
+    if game_state.game_state != GamePlayState::Playing {
+        return;
+    }
+    let mode = GameMode::current(&settings, &daily);
+    let mut text = goal_text.single_mut();
+    text.sections[0].value = mode.goal_text(&game_tick_time).unwrap_or_default();
+}
@@ -5,6 +5,8 @@ pub enum GamePlayState {
     Menu,
     Pause,
     Lose,
+    Win,
+    Upgrade,
     Playing,
 }
 
@@ -30,9 +32,11 @@ impl GameStateInfo {
         match self.game_state {
             GamePlayState::Menu => {
                 match play_state {
-                    GamePlayState::Menu => {} //nothing
-                    GamePlayState::Pause => {}  //nothing shouldnt be able to go here
-                    GamePlayState::Lose => {} //nothing shouldnt be able to go here
+                    GamePlayState::Menu => {}    //nothing
+                    GamePlayState::Pause => {}   //nothing shouldnt be able to go here
+                    GamePlayState::Lose => {}    //nothing shouldnt be able to go here
+                    GamePlayState::Win => {}     //nothing shouldnt be able to go here
+                    GamePlayState::Upgrade => {} //nothing shouldnt be able to go here
                     GamePlayState::Playing => {
                         self.game_state = GamePlayState::Playing;
                         event_writer.send(GamePlayState::Playing)
@@ -45,28 +49,48 @@ impl GameStateInfo {
                         self.game_state = GamePlayState::Menu;
                         event_writer.send(GamePlayState::Menu)
                     } //go to main menu
-                    GamePlayState::Pause => {}  // nothing
-                    GamePlayState::Lose => {} //nothing
+                    GamePlayState::Pause => {}   // nothing
+                    GamePlayState::Lose => {}    //nothing
+                    GamePlayState::Win => {}     //nothing
+                    GamePlayState::Upgrade => {} //nothing
                     GamePlayState::Playing => {
                         self.game_state = GamePlayState::Playing;
                         event_writer.send(GamePlayState::Playing)
                     } // restart game
                 }
             }
-            GamePlayState::Lose => {
+            GamePlayState::Lose | GamePlayState::Win => {
                 match play_state {
                     GamePlayState::Menu => {
                         self.game_state = GamePlayState::Menu;
                         event_writer.send(GamePlayState::Menu)
                     } //go to main menu
-                    GamePlayState::Pause => {}  //nothing
-                    GamePlayState::Lose => {} //nothing
+                    GamePlayState::Pause => {}   //nothing
+                    GamePlayState::Lose => {}    //nothing
+                    GamePlayState::Win => {}     //nothing
+                    GamePlayState::Upgrade => {} //nothing
                     GamePlayState::Playing => {
                         self.game_state = GamePlayState::Playing;
                         event_writer.send(GamePlayState::Playing)
                     } //restart game
                 }
             }
+            GamePlayState::Upgrade => {
+                match play_state {
+                    GamePlayState::Menu => {
+                        self.game_state = GamePlayState::Menu;
+                        event_writer.send(GamePlayState::Menu)
+                    } //end game and go to main menu
+                    GamePlayState::Pause => {}   //nothing
+                    GamePlayState::Lose => {}    //nothing
+                    GamePlayState::Win => {}     //nothing
+                    GamePlayState::Upgrade => {} //nothing
+                    GamePlayState::Playing => {
+                        self.game_state = GamePlayState::Playing;
+                        event_writer.send(GamePlayState::Playing)
+                    } //upgrade picked, carry on
+                }
+            }
             GamePlayState::Playing => {
                 match play_state {
                     GamePlayState::Menu => {
@@ -81,6 +105,14 @@ impl GameStateInfo {
                         self.game_state = GamePlayState::Lose;
                         event_writer.send(GamePlayState::Lose)
                     } //game done and show lose screen
+                    GamePlayState::Win => {
+                        self.game_state = GamePlayState::Win;
+                        event_writer.send(GamePlayState::Win)
+                    } //mode goal reached and show win screen
+                    GamePlayState::Upgrade => {
+                        self.game_state = GamePlayState::Upgrade;
+                        event_writer.send(GamePlayState::Upgrade)
+                    } //stop between waves to pick an upgrade
                     GamePlayState::Playing => {
                         self.game_state = GamePlayState::Playing;
                         event_writer.send(GamePlayState::Playing)
@@ -0,0 +1,257 @@
+use crate::daily::DailyChallenge;
+use crate::definitions_units::Player;
+use crate::enemy_spawner::SpawnRng;
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::menu::{MenuAction, MenuActionEvent};
+use crate::net::NetState;
+use crate::players::FrameControls;
+use crate::settings::GameSettings;
+use crate::{Score, ScoreText};
+use bevy::prelude::*;
+use std::fs;
+
+const GHOST_FILE_PREFIX: &str = "ghost_"; // saved next to the high score table, one file per seed
+const GHOST_ALPHA: f32 = 0.35;
+const GHOST_SIZE: f32 = 16.; // same as the player ship
+const GHOST_Z: f32 = 90.; // just under the players so a ship on top of its ghost stays readable
+
+#[derive(Clone, Copy)]
+struct GhostSample {
+    time: f32, // seconds into the run
+    position: Vec2,
+    angle: f32,
+    score: f32,
+}
+
+/// The path the first player flew in one run, sampled every frame.
+pub struct GhostRun {
+    seed: u64,
+    score: f32, // final score of the run
+    samples: Vec<GhostSample>,
+}
+
+impl GhostRun {
+    fn new(seed: u64) -> GhostRun {
+        GhostRun {
+            seed,
+            score: 0.,
+            samples: Vec::new(),
+        }
+    }
+
+    fn path(seed: u64) -> String {
+        format!("{}{}.txt", GHOST_FILE_PREFIX, seed)
+    }
+
+    /// The saved ghost for `seed`, the final score on the first line and then one
+    /// `time x y angle score` sample per line.
+    fn load(seed: u64) -> Option<GhostRun> {
+        let contents = fs::read_to_string(GhostRun::path(seed)).ok()?;
+        let mut lines = contents.lines();
+        let score = lines.next()?.trim().parse::<f32>().ok()?;
+        let samples = lines
+            .filter_map(|line| {
+                let values: Vec<f32> = line
+                    .split_whitespace()
+                    .filter_map(|value| value.parse::<f32>().ok())
+                    .collect();
+                match values[..] {
+                    [time, x, y, angle, score] => Some(GhostSample {
+                        time,
+                        position: Vec2::new(x, y),
+                        angle,
+                        score,
+                    }),
+                    _ => None,
+                }
+            })
+            .collect();
+        Some(GhostRun {
+            seed,
+            score,
+            samples,
+        })
+    }
+
+    fn save(&self) {
+        let mut contents = format!("{}\n", self.score);
+        for sample in &self.samples {
+            contents.push_str(&format!(
+                "{} {} {} {} {}\n",
+                sample.time, sample.position.x, sample.position.y, sample.angle, sample.score
+            ));
+        }
+        if let Err(error) = fs::write(GhostRun::path(self.seed), contents) {
+            warn!("Failed to save ghost: {}", error);
+        }
+    }
+
+    /// Where the ghost was `time` seconds into its run, `None` once its run is over.
+    fn sample_at(&self, time: f32) -> Option<GhostSample> {
+        let next = self.samples.partition_point(|sample| sample.time <= time);
+        let after = *self.samples.get(next)?;
+        let before = match next.checked_sub(1) {
+            Some(index) => self.samples[index],
+            None => return Some(after),
+        };
+        let progress = (time - before.time) / (after.time - before.time).max(f32::EPSILON);
+        Some(GhostSample {
+            position: before.position.lerp(after.position, progress),
+            ..before
+        })
+    }
+
+    /// The ghost's score `time` seconds into its run, its final score once the run is over.
+    fn score_at(&self, time: f32) -> f32 {
+        self.sample_at(time)
+            .map_or(self.score, |sample| sample.score)
+    }
+}
+
+/// Records every run whose seed can come around again and plays back the best earlier run with the
+/// same seed as a ghost ship. A new recording only replaces the saved ghost when it scored higher.
+#[derive(Default)]
+pub struct Ghosts {
+    waiting_for_run: bool, // a run was started but hasn't simulated yet, the seed isn't final
+    recording: Option<GhostRun>, // None between runs
+    best: Option<GhostRun>,
+    run_time: f32,
+}
+
+#[derive(Component)]
+pub struct GhostShip;
+
+pub fn setup_ghost(mut commands: Commands) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::ONE),
+                ..default()
+            },
+            transform: Transform::from_scale(Vec3::new(GHOST_SIZE, GHOST_SIZE, 1.)),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(GhostShip);
+}
+
+/// Only repeated seeds and daily challenges are played again on the same seed, recording any other
+/// run would leave a file behind that nothing ever plays back.
+fn is_repeatable(settings: &GameSettings, daily: &DailyChallenge, net: &NetState) -> bool {
+    !net.is_online() && (settings.repeat_seed || daily.date.is_some())
+}
+
+/// Starts recording when a run starts and saves the recording when it ends, if it beat the ghost.
+pub fn record_ghost(
+    mut menu_actions: EventReader<MenuActionEvent>,
+    game_state: Res<GameStateInfo>,
+    frame_controls: Res<FrameControls>,
+    spawn_rng: Res<SpawnRng>,
+    score: Res<Score>,
+    players: Query<(&Player, &Transform)>,
+    mut ghosts: ResMut<Ghosts>,
+    settings: Res<GameSettings>,
+    daily: Res<DailyChallenge>,
+    net: Res<NetState>,
+) {
+    for action in menu_actions.iter() {
+        if matches!(
+            action.0,
+            MenuAction::Start | MenuAction::StartDaily | MenuAction::Restart
+        ) {
+            ghosts.waiting_for_run = true;
+            ghosts.recording = None;
+            ghosts.best = None;
+            ghosts.run_time = 0.;
+        }
+    }
+
+    match game_state.game_state {
+        GamePlayState::Playing if frame_controls.simulate => {
+            // network sessions only settle on the seed once the first step is simulated
+            if ghosts.waiting_for_run {
+                ghosts.waiting_for_run = false;
+                if is_repeatable(&settings, &daily, &net) {
+                    ghosts.recording = Some(GhostRun::new(spawn_rng.seed));
+                }
+                ghosts.best = GhostRun::load(spawn_rng.seed);
+            }
+            ghosts.run_time += frame_controls.delta;
+            let time = ghosts.run_time;
+            let recording = match ghosts.recording.as_mut() {
+                Some(recording) => recording,
+                None => return,
+            };
+            // in co-op the ghost follows the first player
+            if let Some((_, transform)) = players.iter().find(|(player, _)| player.id == 0) {
+                let facing = transform.rotation * Vec3::X;
+                recording.samples.push(GhostSample {
+                    time,
+                    position: transform.translation.truncate(),
+                    angle: facing.y.atan2(facing.x),
+                    score: score.score,
+                });
+            }
+        }
+        GamePlayState::Lose | GamePlayState::Win => {
+            if let Some(mut recording) = ghosts.recording.take() {
+                recording.score = score.score;
+                let beat_ghost = ghosts
+                    .best
+                    .as_ref()
+                    .is_none_or(|best| recording.score > best.score);
+                if beat_ghost {
+                    recording.save();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Moves the ghost ship along its recorded path and shows how far ahead of or behind it the score is.
+pub fn update_ghost(
+    ghosts: Res<Ghosts>,
+    game_state: Res<GameStateInfo>,
+    settings: Res<GameSettings>,
+    score: Res<Score>,
+    mut ghost_ship: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<GhostShip>>,
+    mut score_text: Query<&mut Text, With<ScoreText>>,
+) {
+    let best = match (&game_state.game_state, &ghosts.best) {
+        (GamePlayState::Playing, Some(best)) => Some(best),
+        _ => None,
+    };
+    let palette = settings.palette();
+
+    let (mut transform, mut sprite, mut visibility) = ghost_ship.single_mut();
+    match best.and_then(|best| best.sample_at(ghosts.run_time)) {
+        Some(sample) => {
+            visibility.is_visible = true;
+            transform.translation = sample.position.extend(GHOST_Z);
+            transform.rotation = Quat::from_rotation_z(sample.angle);
+            let mut color = palette.player;
+            color.set_a(GHOST_ALPHA);
+            sprite.color = color;
+        }
+        None => visibility.is_visible = false,
+    }
+
+    let mut text = score_text.single_mut();
+    match best {
+        Some(best) => {
+            let delta = score.score - best.score_at(ghosts.run_time);
+            text.sections[2].value = String::from("  GHOST: ");
+            text.sections[3].value = format!("{:+}", delta);
+            text.sections[3].style.color = if delta >= 0. {
+                palette.health
+            } else {
+                palette.enemy
+            };
+        }
+        None => {
+            text.sections[2].value.clear();
+            text.sections[3].value.clear();
+        }
+    }
+}
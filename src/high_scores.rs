@@ -0,0 +1,82 @@
+use crate::game_mode::GameMode;
+use bevy::prelude::*;
+use std::fs;
+
+const MAX_HIGH_SCORES: usize = 10;
+
+pub struct HighScoreEntry {
+    pub score: f32,
+}
+
+/// Local high score tables, one per game mode, each kept sorted from best to worst and saved to disk
+/// one score per line.
+pub struct HighScores {
+    tables: [Vec<HighScoreEntry>; GameMode::ALL.len()], // indexed by `GameMode::index`
+}
+
+impl FromWorld for HighScores {
+    fn from_world(_world: &mut World) -> Self {
+        HighScores::load()
+    }
+}
+
+impl HighScores {
+    fn file(mode: GameMode) -> &'static str {
+        match mode {
+            GameMode::Endless => "high_scores.txt", // from before there were modes
+            GameMode::TimeAttack => "high_scores_time_attack.txt",
+            GameMode::Target => "high_scores_target.txt",
+        }
+    }
+
+    pub fn load() -> HighScores {
+        HighScores {
+            tables: GameMode::ALL.map(HighScores::load_table),
+        }
+    }
+
+    fn load_table(mode: GameMode) -> Vec<HighScoreEntry> {
+        let mut entries: Vec<HighScoreEntry> = match fs::read_to_string(HighScores::file(mode)) {
+            Ok(contents) => contents
+                .lines()
+                .filter_map(|line| line.trim().parse::<f32>().ok())
+                .map(|score| HighScoreEntry { score })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        entries.sort_by(|a, b| b.score.total_cmp(&a.score));
+        entries.truncate(MAX_HIGH_SCORES);
+        entries
+    }
+
+    pub fn entries(&self, mode: GameMode) -> &[HighScoreEntry] {
+        &self.tables[mode.index()]
+    }
+
+    pub fn save(&self, mode: GameMode) {
+        let contents: String = self
+            .entries(mode)
+            .iter()
+            .map(|entry| format!("{}\n", entry.score))
+            .collect();
+        if let Err(error) = fs::write(HighScores::file(mode), contents) {
+            warn!("Failed to save high scores: {}", error);
+        }
+    }
+
+    /// Adds the score to the mode's table and saves it. Returns the place it got, if it made it in.
+    pub fn submit(&mut self, mode: GameMode, score: f32) -> Option<usize> {
+        let entries = &mut self.tables[mode.index()];
+        let place = entries
+            .iter()
+            .position(|entry| score > entry.score)
+            .unwrap_or(entries.len());
+        if place >= MAX_HIGH_SCORES {
+            return None;
+        }
+        entries.insert(place, HighScoreEntry { score });
+        entries.truncate(MAX_HIGH_SCORES);
+        self.save(mode);
+        Some(place)
+    }
+}
@@ -0,0 +1,527 @@
+use crate::config::GameConfig;
+use crate::daily::DailyChallenge;
+use crate::damage::Hitpoints;
+use crate::definitions_units::{
+    Player, PlayerStats, MAX_HEALTH, MAX_KILL_MODE_TIME, MAX_OVERHEAL_HEALTH, PLAYER_COLOR,
+    POWERUP_COLOR,
+};
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::players::{player_count, MAX_PLAYERS};
+use crate::powerups::{warning_flash, PowerupKind};
+use crate::settings::GameSettings;
+use crate::{GameTickInfo, FONT_ASSET_PATH};
+use bevy::prelude::*;
+
+const DAMAGE_FLASH_TIME: f32 = 0.3;
+const POWERUP_ICON_FONT_SIZE: f32 = 20.;
+
+const HUD_BACKGROUND_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const HUD_EMPTY_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.12);
+const OVERHEAL_WHITENESS: f32 = 0.5; // overheal segments are the health color mixed towards white
+const DAMAGE_FLASH_COLOR: Color = Color::rgb(1.0, 1.0, 1.0);
+const TICK_BAR_COLOR: Color = Color::rgb(0.5, 0.5, 1.0);
+const DASH_READY_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.9);
+const DASH_CHARGING_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.35);
+
+#[derive(Component)]
+pub struct HudWidget; // every hud node, used to show and hide the whole hud
+#[derive(Component)]
+pub struct HudSlot(usize); // every node of one player's panel, the `Player::id` it shows
+#[derive(Component)]
+pub struct HealthBar {
+    last_health: i32,
+    flash_time_left: f32,
+}
+#[derive(Component)]
+pub struct HealthBarSegment(i32); // index of the segment from the left, past MAX_HEALTH is overheal
+#[derive(Component)]
+pub struct KillModeBar;
+#[derive(Component)]
+pub struct KillModeBarFill;
+#[derive(Component)]
+pub struct TickBarFill;
+#[derive(Component)]
+pub struct DashBarFill;
+#[derive(Component)]
+pub struct PowerupIcon(PowerupKind); // on both the icon node and its text, shown while the powerup runs
+
+/// Builds the playing hud, a panel per possible player along the bottom with the ones not playing
+/// hidden. Everything is sized in percent of the screen so it lays out the same at any resolution.
+pub fn setup_hud(commands: &mut Commands, asset_server: &mut ResMut<AssetServer>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                // column reverse goes from top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::SpaceBetween,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: UiColor(Color::NONE),
+            ..default()
+        })
+        .insert(HudWidget)
+        .with_children(|parent| {
+            // tick progress, fills up until the next tick
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(40.), Val::Percent(1.)),
+                        margin: UiRect {
+                            top: Val::Percent(1.),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    color: UiColor(HUD_BACKGROUND_COLOR),
+                    ..default()
+                })
+                .insert(HudWidget)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.), Val::Percent(100.)),
+                                ..default()
+                            },
+                            color: UiColor(TICK_BAR_COLOR),
+                            ..default()
+                        })
+                        .insert(TickBarFill)
+                        .insert(HudWidget);
+                });
+
+            // one panel per player side by side along the bottom
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Percent(12.)),
+                        margin: UiRect {
+                            bottom: Val::Percent(2.),
+                            ..default()
+                        },
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    color: UiColor(Color::NONE),
+                    ..default()
+                })
+                .insert(HudWidget)
+                .with_children(|parent| {
+                    for slot in 0..MAX_PLAYERS {
+                        spawn_player_panel(parent, asset_server, slot);
+                    }
+                });
+        });
+}
+
+/// The hud of one player, their active powerups above the health, kill mode and dash bars.
+fn spawn_player_panel(
+    parent: &mut ChildBuilder,
+    asset_server: &mut ResMut<AssetServer>,
+    slot: usize,
+) {
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(24.), Val::Percent(100.)),
+                margin: UiRect {
+                    left: Val::Percent(0.5),
+                    right: Val::Percent(0.5),
+                    ..default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::SpaceBetween,
+                ..default()
+            },
+            color: UiColor(Color::NONE),
+            ..default()
+        })
+        .insert(HudWidget)
+        .insert(HudSlot(slot))
+        .with_children(|parent| {
+            // row of active powerups
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Percent(33.)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: UiColor(Color::NONE),
+                    ..default()
+                })
+                .insert(HudWidget)
+                .insert(HudSlot(slot))
+                .with_children(|parent| {
+                    for kind in PowerupKind::ALL {
+                        if kind == PowerupKind::Shockwave {
+                            continue; // instant, nothing to show
+                        }
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    padding: UiRect::all(Val::Px(4.)),
+                                    margin: UiRect::all(Val::Px(3.)),
+                                    ..default()
+                                },
+                                color: UiColor(HUD_BACKGROUND_COLOR),
+                                ..default()
+                            })
+                            .insert(PowerupIcon(kind))
+                            .insert(HudWidget)
+                            .insert(HudSlot(slot))
+                            .with_children(|parent| {
+                                parent
+                                    .spawn_bundle(TextBundle::from_section(
+                                        kind.icon(),
+                                        TextStyle {
+                                            font: asset_server.load(FONT_ASSET_PATH),
+                                            font_size: POWERUP_ICON_FONT_SIZE,
+                                            color: POWERUP_COLOR,
+                                        },
+                                    ))
+                                    .insert(PowerupIcon(kind))
+                                    .insert(HudWidget)
+                                    .insert(HudSlot(slot));
+                            });
+                    }
+                });
+
+            // health, kill mode and dash bars
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Percent(67.)),
+                        flex_direction: FlexDirection::ColumnReverse,
+                        justify_content: JustifyContent::SpaceBetween,
+                        ..default()
+                    },
+                    color: UiColor(Color::NONE),
+                    ..default()
+                })
+                .insert(HudWidget)
+                .insert(HudSlot(slot))
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Percent(55.)),
+                                padding: UiRect::all(Val::Px(3.)),
+                                ..default()
+                            },
+                            color: UiColor(HUD_BACKGROUND_COLOR),
+                            ..default()
+                        })
+                        .insert(HealthBar {
+                            last_health: MAX_HEALTH,
+                            flash_time_left: 0.,
+                        })
+                        .insert(HudWidget)
+                        .insert(HudSlot(slot))
+                        .with_children(|parent| {
+                            for index in 0..MAX_OVERHEAL_HEALTH {
+                                parent
+                                    .spawn_bundle(NodeBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Auto, Val::Percent(100.)),
+                                            flex_grow: 1.,
+                                            margin: UiRect::all(Val::Px(2.)),
+                                            ..default()
+                                        },
+                                        color: UiColor(PLAYER_COLOR),
+                                        ..default()
+                                    })
+                                    .insert(HealthBarSegment(index))
+                                    .insert(HudWidget)
+                                    .insert(HudSlot(slot));
+                            }
+                        });
+
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Percent(20.)),
+                                ..default()
+                            },
+                            color: UiColor(HUD_BACKGROUND_COLOR),
+                            ..default()
+                        })
+                        .insert(KillModeBar)
+                        .insert(HudWidget)
+                        .insert(HudSlot(slot))
+                        .with_children(|parent| {
+                            parent
+                                .spawn_bundle(NodeBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Percent(0.), Val::Percent(100.)),
+                                        ..default()
+                                    },
+                                    color: UiColor(POWERUP_COLOR),
+                                    ..default()
+                                })
+                                .insert(KillModeBarFill)
+                                .insert(HudWidget)
+                                .insert(HudSlot(slot));
+                        });
+
+                    // dash cooldown, full when the dash is ready
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Percent(15.)),
+                                ..default()
+                            },
+                            color: UiColor(HUD_BACKGROUND_COLOR),
+                            ..default()
+                        })
+                        .insert(HudWidget)
+                        .insert(HudSlot(slot))
+                        .with_children(|parent| {
+                            parent
+                                .spawn_bundle(NodeBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                                        ..default()
+                                    },
+                                    color: UiColor(DASH_READY_COLOR),
+                                    ..default()
+                                })
+                                .insert(DashBarFill)
+                                .insert(HudWidget)
+                                .insert(HudSlot(slot));
+                        });
+                });
+        });
+}
+
+/// Stats of the player a hud slot belongs to, `None` once they are out of the run.
+fn slot_stats<'a>(
+    players: &'a Query<(&Player, &PlayerStats, &Hitpoints)>,
+    slot: &HudSlot,
+) -> Option<&'a PlayerStats> {
+    players
+        .iter()
+        .find(|(player, _, _)| player.id == slot.0)
+        .map(|(_, player_stats, _)| player_stats)
+}
+
+/// What the hud icon of a powerup should say, `None` when it isn't running.
+fn powerup_status(player_stats: &PlayerStats, kind: PowerupKind) -> Option<String> {
+    if kind == PowerupKind::Shield && player_stats.powerups.shield_hits > 0 {
+        return Some(format!(
+            "{} x{}",
+            kind.icon(),
+            player_stats.powerups.shield_hits
+        ));
+    }
+    if kind == PowerupKind::WeaponUpgrade && player_stats.weapon.level > 0 {
+        return Some(format!("{} {}", kind.icon(), player_stats.weapon.level + 1));
+    }
+    match player_stats.powerup_time_left(kind) {
+        Some(time_left) if time_left > 0. => Some(format!("{} {:.1}", kind.icon(), time_left)),
+        _ => None,
+    }
+}
+
+pub fn update_hud_visibility(
+    game_state: Res<GameStateInfo>,
+    settings: Res<GameSettings>,
+    daily: Res<DailyChallenge>,
+    players: Query<(&Player, &PlayerStats, &Hitpoints)>,
+    mut hud_query: Query<
+        (
+            &mut Visibility,
+            Option<&HudSlot>,
+            Option<&KillModeBar>,
+            Option<&KillModeBarFill>,
+            Option<&PowerupIcon>,
+        ),
+        With<HudWidget>,
+    >,
+) {
+    for (mut visibility, slot, kill_mode_bar, kill_mode_bar_fill, powerup_icon) in
+        hud_query.iter_mut()
+    {
+        match game_state.game_state {
+            GamePlayState::Menu => {
+                visibility.is_visible = false;
+            }
+            GamePlayState::Pause | GamePlayState::Upgrade => {
+                visibility.is_visible = false;
+            }
+            GamePlayState::Lose | GamePlayState::Win => {
+                visibility.is_visible = false;
+            }
+            GamePlayState::Playing => {
+                visibility.is_visible = true;
+                let slot = match slot {
+                    Some(slot) => slot,
+                    None => continue,
+                };
+                // panels of players who aren't in this run stay hidden, the player count can only
+                // change from the main menu so it matches the run
+                if slot.0 >= player_count(&settings, &daily) {
+                    visibility.is_visible = false;
+                    continue;
+                }
+                let player_stats = slot_stats(&players, slot);
+                // the kill mode bar only shows up while kill mode is running
+                if kill_mode_bar.is_some() || kill_mode_bar_fill.is_some() {
+                    visibility.is_visible =
+                        player_stats.is_some_and(|player_stats| player_stats.kill_mode);
+                }
+                if let Some(icon) = powerup_icon {
+                    visibility.is_visible = player_stats
+                        .and_then(|player_stats| powerup_status(player_stats, icon.0))
+                        .is_some();
+                }
+            }
+        }
+    }
+}
+
+pub fn update_health_bar(
+    time: Res<Time>,
+    players: Query<(&Player, &Hitpoints)>,
+    settings: Res<GameSettings>,
+    mut health_bars: Query<(&HudSlot, &mut HealthBar, &mut UiColor), Without<HealthBarSegment>>,
+    mut segments: Query<(&HudSlot, &HealthBarSegment, &mut UiColor), Without<HealthBar>>,
+) {
+    // players who are out of the run show an empty bar
+    let mut health = [0; MAX_PLAYERS];
+    let mut max_health = [MAX_HEALTH; MAX_PLAYERS]; // upgrades can raise it
+    for (player, hitpoints) in players.iter() {
+        health[player.id] = hitpoints.current;
+        max_health[player.id] = hitpoints.max;
+    }
+
+    let mut flash = [0.; MAX_PLAYERS];
+    for (slot, mut health_bar, mut bar_color) in health_bars.iter_mut() {
+        if health[slot.0] < health_bar.last_health {
+            health_bar.flash_time_left = DAMAGE_FLASH_TIME;
+        }
+        health_bar.last_health = health[slot.0];
+        health_bar.flash_time_left = (health_bar.flash_time_left - time.delta_seconds()).max(0.);
+
+        flash[slot.0] = health_bar.flash_time_left / DAMAGE_FLASH_TIME;
+        bar_color.0 = lerp_color(
+            HUD_BACKGROUND_COLOR,
+            DAMAGE_FLASH_COLOR,
+            flash[slot.0] * 0.6,
+        );
+    }
+
+    let palette = settings.palette();
+    let overheal_color = lerp_color(palette.health, Color::WHITE, OVERHEAL_WHITENESS);
+    for (slot, segment, mut color) in segments.iter_mut() {
+        let overheal = segment.0 >= max_health[slot.0];
+        color.0 = if segment.0 < health[slot.0] {
+            let full_color = if overheal {
+                overheal_color
+            } else {
+                palette.player
+            };
+            lerp_color(full_color, DAMAGE_FLASH_COLOR, flash[slot.0])
+        } else if overheal {
+            // empty overheal slots stay hidden, they only show up while overhealed
+            Color::NONE
+        } else {
+            HUD_EMPTY_COLOR
+        };
+    }
+}
+
+pub fn update_kill_mode_bar(
+    time: Res<Time>,
+    players: Query<(&Player, &PlayerStats, &Hitpoints)>,
+    settings: Res<GameSettings>,
+    mut kill_mode_fills: Query<(&HudSlot, &mut Style, &mut UiColor), With<KillModeBarFill>>,
+) {
+    for (slot, mut style, mut color) in kill_mode_fills.iter_mut() {
+        let (time_left, max_time) =
+            slot_stats(&players, slot).map_or((0., MAX_KILL_MODE_TIME), |player_stats| {
+                (
+                    player_stats.time_left_in_kill_mode,
+                    player_stats.max_kill_mode_time(),
+                )
+            });
+        color.0 = if warning_flash(time_left, time.seconds_since_startup() as f32) {
+            DAMAGE_FLASH_COLOR
+        } else {
+            settings.palette().powerup
+        };
+        let fraction = (time_left / max_time).clamp(0., 1.);
+        style.size.width = Val::Percent(fraction * 100.);
+    }
+}
+
+pub fn update_powerup_icons(
+    time: Res<Time>,
+    players: Query<(&Player, &PlayerStats, &Hitpoints)>,
+    settings: Res<GameSettings>,
+    mut icon_texts: Query<(&HudSlot, &PowerupIcon, &mut Text)>,
+) {
+    let color = settings.palette().powerup;
+    let elapsed = time.seconds_since_startup() as f32;
+    for (slot, icon, mut text) in icon_texts.iter_mut() {
+        let player_stats = match slot_stats(&players, slot) {
+            Some(player_stats) => player_stats,
+            None => continue,
+        };
+        if let Some(status) = powerup_status(player_stats, icon.0) {
+            let time_left = player_stats.powerup_time_left(icon.0).unwrap_or(0.);
+            text.sections[0].value = status;
+            text.sections[0].style.color = if warning_flash(time_left, elapsed) {
+                DAMAGE_FLASH_COLOR
+            } else {
+                color
+            };
+        }
+    }
+}
+
+pub fn update_dash_bar(
+    players: Query<(&Player, &PlayerStats, &Hitpoints)>,
+    config: Res<GameConfig>,
+    mut dash_fills: Query<(&HudSlot, &mut Style, &mut UiColor), With<DashBarFill>>,
+) {
+    for (slot, mut style, mut color) in dash_fills.iter_mut() {
+        let cooldown_left =
+            slot_stats(&players, slot).map_or(0., |player_stats| player_stats.dash_cooldown_left);
+        let fraction = if config.dash_cooldown > 0. {
+            (1. - cooldown_left / config.dash_cooldown).clamp(0., 1.)
+        } else {
+            1.
+        };
+        style.size.width = Val::Percent(fraction * 100.);
+        color.0 = if fraction >= 1. {
+            DASH_READY_COLOR
+        } else {
+            DASH_CHARGING_COLOR
+        };
+    }
+}
+
+pub fn update_tick_bar(
+    game_tick_time: Res<GameTickInfo>,
+    mut tick_fill: Query<&mut Style, With<TickBarFill>>,
+) {
+    let mut style = tick_fill.single_mut();
+    let fraction =
+        (game_tick_time.time_till_next_tick / game_tick_time.time_between_ticks).clamp(0., 1.);
+    style.size.width = Val::Percent(fraction * 100.);
+}
+
+fn lerp_color(from: Color, to: Color, amount: f32) -> Color {
+    let amount = amount.clamp(0., 1.);
+    Color::rgba(
+        from.r() + (to.r() - from.r()) * amount,
+        from.g() + (to.g() - from.g()) * amount,
+        from.b() + (to.b() - from.b()) * amount,
+        from.a() + (to.a() - from.a()) * amount,
+    )
+}
@@ -1,20 +1,64 @@
+mod boss;
+mod config;
+mod daily;
+mod damage;
+mod dash;
 mod definitions_units;
 mod enemy_spawner;
+mod flight;
+mod game_mode;
 mod game_state;
 mod generic_components;
+mod ghost;
+mod high_scores;
+mod hud;
 mod map;
-
+mod menu;
+mod minimap;
+mod net;
+mod offscreen_indicators;
+mod particles;
+mod pickups;
+mod players;
+mod popups;
+mod powerups;
+mod profile;
+mod projectiles;
+mod settings;
+mod theme;
+mod upgrades;
+mod weapon;
+
+use crate::boss::Boss;
+use crate::config::{GameConfig, InputBindings};
+use crate::daily::{DailyChallenge, DailyDate, DailyResults};
+use crate::damage::{DamageEvent, DamageSource, DeathEvent, Hitpoints};
 use crate::definitions_units::{
-    Enemy, Health, Player, PlayerStats, Powerup, ENEMY_COLOR, PLAYER_COLOR, POWERUP_COLOR,
+    Enemy, Health, Player, PlayerStats, Powerup, RangedEnemy, ENEMY_COLOR, HEALTH_PICKUP_HEAL,
+    PLAYER_COLOR, RANGED_ENEMY_FIRE_INTERVAL, RANGED_ENEMY_MAX_DISTANCE,
+    RANGED_ENEMY_MIN_DISTANCE, RANGED_ENEMY_PROJECTILE_SPEED,
 };
-use crate::enemy_spawner::{SpawnEvents, Spawner};
+use crate::enemy_spawner::{SpawnEvents, SpawnRng, Spawner};
+use crate::flight::{fly_momentum, FlightModel};
+use crate::game_mode::GameMode;
 use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::high_scores::HighScores;
+use crate::menu::{MenuAction, MenuActionEvent, MenuInfo, MenuPage};
+use crate::net::{NetState, RunSetup};
+use crate::particles::{ParticleEffect, ParticleEvent};
+use crate::players::FrameControls;
+use crate::popups::PopupEvent;
+use crate::powerups::{shockwave, PowerupKind, Staggered};
+use crate::projectiles::{Projectile, ProjectileEvent, ProjectileTeam};
+use crate::settings::{GameSettings, MusicChannel, SettingKind, MUSIC_VOLUME};
+use crate::theme::{PaletteText, ThemedKind};
 use bevy::app::AppExit;
 use bevy::prelude::*;
-use bevy::time::FixedTimestep;
 use bevy::window::{close_on_esc, WindowMode};
 use bevy_kira_audio::*;
 use bevy_rapier2d::prelude::*;
+use rand::seq::IteratorRandom;
+use rand::{thread_rng, Rng};
 const HEALTH_PICKUP_ASSET_PATH: &str = ("483602__raclure__game-bump.mp3");
 const POWERUP_PICKUP_ASSET_PATH: &str = ("344522__jeremysykes__powerup05.wav");
 const ENEMY_COLLISION_KILL_SOUND_ASSET_PATH: &str = ("242857__plasterbrain__coin-get.ogg");
@@ -25,6 +69,7 @@ const PLAYER_TURN_SOUND_ASSET_PATH: &str = ("483602__raclure__game-bump.mp3");
 const BG_MUSIC_ASSET_PATH: &str = ("483602__raclure__game-bump.mp3");
 
 const TIME_STEP: f32 = 1.0 / 30.0;
+const CONTROLS_STAGE: &str = "controls";
 const MAX_OBJECT_DISTANCE: f32 = 3000.;
 
 const FONT_ASSET_PATH: &str = ("OpenSans-ExtraBold.ttf");
@@ -54,6 +99,7 @@ fn main() {
         //plugins and tools
         .add_plugins(DefaultPlugins)
         .add_plugin(AudioPlugin)
+        .add_audio_channel::<MusicChannel>()
         // bevy rapier
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(32.0))
         //.add_plugin(RapierDebugRenderPlugin::default())
@@ -62,42 +108,128 @@ fn main() {
         //.add_startup_system(map::setup_map)
         // events
         .add_event::<TickEvent>()
-        .add_event::<HealthGone>()
+        .add_event::<DamageEvent>()
+        .add_event::<DeathEvent>()
         .add_event::<GamePlayState>()
         .add_event::<SpawnEvents>()
+        .add_event::<PopupEvent>()
+        .add_event::<MenuActionEvent>()
+        .add_event::<ParticleEvent>()
+        .add_event::<ProjectileEvent>()
         // resources
         .init_resource::<GameTickInfo>()
         .init_resource::<GameStateInfo>()
         .init_resource::<Spawner>()
-        .init_resource::<PlayerInput>()
-        .init_resource::<PlayerStats>()
         .init_resource::<Score>()
+        .init_resource::<minimap::MinimapSettings>()
+        .init_resource::<GameSettings>()
+        .init_resource::<HighScores>()
+        .init_resource::<DailyChallenge>()
+        .init_resource::<DailyResults>()
+        .init_resource::<MenuInfo>()
+        .init_resource::<theme::ThemeAssets>()
+        .init_resource::<GameConfig>()
+        .init_resource::<InputBindings>()
+        .init_resource::<FrameControls>()
+        .init_resource::<NetState>()
+        .init_resource::<SpawnRng>()
+        .init_resource::<ghost::Ghosts>()
+        .init_resource::<upgrades::UpgradeOffer>()
+        .init_resource::<upgrades::UpgradeBuild>()
+        .init_resource::<profile::Profile>()
+        // controls are gathered after bevy's own input handling and before anything uses them
+        .add_stage_after(
+            CoreStage::PreUpdate,
+            CONTROLS_STAGE,
+            SystemStage::parallel(),
+        )
+        .add_system_to_stage(CONTROLS_STAGE, players::gather_controls)
+        .add_system_to_stage(CONTROLS_STAGE, net::run_lockstep)
+        // decided after `Update` so a state change this frame already holds physics still
+        .add_system_to_stage(PhysicsStages::SyncBackend, pause_physics)
         // startup systems
         .add_startup_system(setup_game_core)
         .add_startup_system(setup_player)
+        .add_startup_system(net::setup_net)
+        .add_startup_system(minimap::setup_minimap)
+        .add_startup_system(popups::setup_popups)
+        .add_startup_system(ghost::setup_ghost)
+        .add_startup_system(projectiles::setup_projectiles)
         //
+        // the simulation, stepped by `TIME_STEP` and in one fixed order so network peers agree
         .add_system_set(
             SystemSet::new()
-                .with_run_criteria(FixedTimestep::step(TIME_STEP as f64))
-                .with_system(handle_enemy_ai),
+                .with_run_criteria(net::simulation_step)
+                .with_system(game_tick_manager)
+                .with_system(handle_tick_events.after(game_tick_manager))
+                .with_system(boss::spawn_bosses.after(handle_tick_events))
+                .with_system(handle_spawn_events.after(boss::spawn_bosses))
+                .with_system(player_movement.after(handle_spawn_events))
+                .with_system(dash::player_dash.after(player_movement))
+                .with_system(powerups::update_status_timers.after(dash::player_dash))
+                .with_system(handle_enemy_ai.after(powerups::update_status_timers))
+                .with_system(boss::handle_boss_ai.after(handle_enemy_ai))
+                .with_system(weapon::fire_weapons.after(boss::handle_boss_ai))
+                .with_system(pickups::attract_pickups.after(weapon::fire_weapons))
+                .with_system(projectiles::handle_projectile_events.after(pickups::attract_pickups))
+                .with_system(handle_player_colliding.after(projectiles::handle_projectile_events))
+                .with_system(boss::handle_boss_contact.after(handle_player_colliding))
+                .with_system(projectiles::handle_projectile_hits.after(boss::handle_boss_contact))
+                .with_system(damage::apply_damage.after(projectiles::handle_projectile_hits))
+                .with_system(handle_enemy_deaths.after(damage::apply_damage))
+                .with_system(boss::handle_boss_deaths.after(handle_enemy_deaths))
+                .with_system(handle_player_death.after(boss::handle_boss_deaths))
+                .with_system(handle_mode_goal.after(handle_player_death))
+                // a death on the tick that offers an upgrade ends the run instead
+                .with_system(upgrades::offer_upgrades.after(handle_mode_goal))
+                .with_system(net::sync_simulation.after(upgrades::offer_upgrades)),
         )
         //generic loop systems
-        .add_system(game_tick_manager)
-        .add_system(handle_tick_events)
-        .add_system(handle_spawn_events)
-        .add_system(handle_player_colliding)
+        // also parks the projectiles of a run that ended, which has to happen while nothing steps
+        .add_system(projectiles::update_projectiles.after(projectiles::handle_projectile_hits))
+        .add_system(boss::update_boss_bar)
         .add_system(update_ui)
-        .add_system(handle_player_death)
-        .add_system(player_menu_controls)
+        .add_system(hud::update_hud_visibility)
+        .add_system(hud::update_health_bar)
+        .add_system(hud::update_kill_mode_bar)
+        .add_system(hud::update_tick_bar)
+        .add_system(hud::update_powerup_icons)
+        .add_system(hud::update_dash_bar)
+        .add_system(offscreen_indicators::update_offscreen_indicators)
+        .add_system(minimap::update_minimap)
+        .add_system(popups::handle_popup_events)
+        .add_system(popups::animate_popups)
+        .add_system(particles::handle_particle_events)
+        .add_system(particles::update_particles)
+        .add_system(particles::emit_thrust_trail)
+        .add_system(ghost::record_ghost)
+        .add_system(ghost::update_ghost)
+        .add_system(game_mode::update_mode_goal_text)
+        .add_system(upgrades::handle_upgrade_choice)
+        .add_system(upgrades::update_build_text)
+        .add_system(profile::award_credits)
+        .add_system(profile::apply_loadout)
+        .add_system(profile::apply_powerup_unlocks)
+        .add_system(profile::handle_shop_actions)
+        .add_system(profile::update_credits_earned_text)
+        .add_system(menu::menu_navigation)
+        .add_system(menu::update_menus)
+        .add_system(handle_menu_actions)
+        .add_system(settings::apply_audio_settings)
+        // a weapon setting taken from the network host has to reach the spawner before the run ticks
+        .add_system(weapon::apply_weapon_setting.before(game_tick_manager))
+        .add_system(theme::apply_theme)
+        .add_system(theme::apply_palette_to_text)
+        .add_system(theme::apply_high_contrast_shapes)
         // specialized systems
-        .add_system(player_movement)
+        .add_system(players::frame_players)
         //
         .run();
 }
 
 enum AudioType {
     HealthPickup,
-    PowerupPickup,
+    PowerupPickup(PowerupKind),
     EnemyCollisionDamage,
     EnemyCollisionKillMode,
     Death,
@@ -109,7 +241,7 @@ impl AudioType {
     pub fn return_asset_path(&self) -> &str {
         match self {
             AudioType::HealthPickup => HEALTH_PICKUP_ASSET_PATH,
-            AudioType::PowerupPickup => POWERUP_PICKUP_ASSET_PATH,
+            AudioType::PowerupPickup(_) => POWERUP_PICKUP_ASSET_PATH,
             AudioType::EnemyCollisionDamage => ENEMY_COLLISION_DAMAGE_SOUND_ASSET_PATH,
             AudioType::EnemyCollisionKillMode => ENEMY_COLLISION_DAMAGE_SOUND_ASSET_PATH,
             AudioType::Death => DEATH_SOUND_ASSET_PATH,
@@ -119,20 +251,12 @@ impl AudioType {
     }
 }
 
+#[derive(Component, Default)]
 pub struct PlayerInput {
     pub is_holding_forward: bool,
     pub is_holding_turn: bool,
 }
 
-impl FromWorld for PlayerInput {
-    fn from_world(world: &mut World) -> Self {
-        PlayerInput {
-            is_holding_forward: false,
-            is_holding_turn: false,
-        }
-    }
-}
-
 pub struct Score {
     score: f32,
 }
@@ -145,18 +269,12 @@ impl FromWorld for Score {
 
 #[derive(Default)]
 struct TickEvent {}
-#[derive(Default)]
-pub struct HealthGone {}
 
 #[derive(Component)]
 pub struct PlayingText; //used to enable and disable all playing text
 #[derive(Component)]
 pub struct RunText;
 #[derive(Component)]
-pub struct HealthText;
-#[derive(Component)]
-pub struct PowerupText;
-#[derive(Component)]
 pub struct ScoreText;
 #[derive(Component)]
 pub struct MenuText; // used to enable and disable menu text when in menu
@@ -164,16 +282,14 @@ pub struct MenuText; // used to enable and disable menu text when in menu
 pub struct LoseScoreText; //updated to show final score at end of game
 #[derive(Component)]
 pub struct LoseText; //all lose text to enable and disable lose text at end of game
-#[derive(Component)]
-pub struct PauseText; //all lose text to enable and disable lose text at end of game
-#[derive(Component)]
-pub struct ResumeText;
 
 pub struct GameTickInfo {
     do_tick: bool,
     base_time_between_ticks: f32,
     time_between_ticks: f32,
     time_till_next_tick: f32,
+    run_time: f32, // seconds played in this run
+    ticks: u32,    // ticks in this run
 }
 impl FromWorld for GameTickInfo {
     fn from_world(world: &mut World) -> Self {
@@ -182,6 +298,8 @@ impl FromWorld for GameTickInfo {
             base_time_between_ticks: 1.,
             time_between_ticks: 1.,
             time_till_next_tick: 0.,
+            run_time: 0.,
+            ticks: 0,
         }
     }
 }
@@ -189,77 +307,29 @@ impl FromWorld for GameTickInfo {
 fn setup_game_core(
     mut commands: Commands,
     mut asset_server: ResMut<AssetServer>,
-    audio: Res<Audio>,
+    music: Res<AudioChannel<MusicChannel>>,
 ) {
     commands
         .spawn_bundle(Camera2dBundle::default())
         .insert(definitions_units::PlayerCam);
     setup_playing_ui(&mut commands, &mut asset_server);
+    hud::setup_hud(&mut commands, &mut asset_server);
+    boss::setup_boss_bar(&mut commands);
+    offscreen_indicators::setup_offscreen_indicators(&mut commands, &mut asset_server);
     setup_menu_ui(&mut commands, &mut asset_server);
     setup_lose_ui(&mut commands, &mut asset_server);
-    audio
+    game_mode::setup_mode_goal_text(&mut commands, &mut asset_server);
+    upgrades::setup_build_text(&mut commands, &mut asset_server);
+    profile::setup_credits_earned_text(&mut commands, &mut asset_server);
+    menu::setup_menus(&mut commands, &mut asset_server);
+    music
         .play(asset_server.load(
             "651183__josefpres__8-bit-music-loop-002-part-02-simple-mix-02-short-loop-120-bpm.wav",
         ))
-        .with_volume(0.2)
+        .with_volume(MUSIC_VOLUME)
         .loop_from(0.5);
 }
 fn setup_playing_ui(mut commands: &mut Commands, asset_server: &mut ResMut<AssetServer>) {
-    commands
-        .spawn_bundle(
-            // Create a TextBundle that has a Text with a single section.
-            TextBundle::from_section(
-                // Accepts a `String` or any type that converts into a `String`, such as `&str`
-                "10",
-                TextStyle {
-                    font: asset_server.load(FONT_ASSET_PATH),
-                    font_size: HEALTH_FONT_SIZE - 10.,
-                    color: PLAYER_COLOR,
-                },
-            ) // Set the alignment of the Text
-            .with_text_alignment(TextAlignment::TOP_CENTER)
-            // Set the style of the TextBundle itself.
-            .with_style(Style {
-                align_self: AlignSelf::FlexEnd,
-                position_type: PositionType::Absolute,
-                position: UiRect {
-                    top: Val::Percent(52.),
-                    left: Val::Percent(48.),
-                    ..default()
-                },
-                ..default()
-            }),
-        )
-        .insert(HealthText)
-        .insert(PlayingText);
-
-    commands
-        .spawn_bundle(
-            // Create a TextBundle that has a Text with a single section.
-            TextBundle::from_section(
-                // Accepts a `String` or any type that converts into a `String`, such as `&str`
-                "10",
-                TextStyle {
-                    font: asset_server.load(FONT_ASSET_PATH),
-                    font_size: HEALTH_FONT_SIZE - 10.,
-                    color: POWERUP_COLOR,
-                },
-            ) // Set the alignment of the Text
-            .with_text_alignment(TextAlignment::TOP_CENTER)
-            // Set the style of the TextBundle itself.
-            .with_style(Style {
-                align_self: AlignSelf::FlexEnd,
-                position_type: PositionType::Absolute,
-                position: UiRect {
-                    top: Val::Percent(52.),
-                    left: Val::Percent(51.),
-                    ..default()
-                },
-                ..default()
-            }),
-        )
-        .insert(PowerupText)
-        .insert(PlayingText);
     commands
         .spawn_bundle(
             // Create a TextBundle that has a Text with a single section.
@@ -286,6 +356,7 @@ fn setup_playing_ui(mut commands: &mut Commands, asset_server: &mut ResMut<Asset
             }),
         )
         .insert(RunText)
+        .insert(PaletteText(ThemedKind::Enemy))
         .insert(PlayingText);
 
     commands
@@ -333,61 +404,6 @@ fn setup_playing_ui(mut commands: &mut Commands, asset_server: &mut ResMut<Asset
         .insert(PlayingText);
 }
 fn setup_menu_ui(mut commands: &mut Commands, asset_server: &mut ResMut<AssetServer>) {
-    commands
-        .spawn_bundle(
-            // Create a TextBundle that has a Text with a single section.
-            TextBundle::from_section(
-                // Accepts a `String` or any type that converts into a `String`, such as `&str`
-                "Press (Escape) to quit",
-                TextStyle {
-                    font: asset_server.load(FONT_ASSET_PATH),
-                    font_size: HEALTH_FONT_SIZE,
-                    color: Color::WHITE,
-                },
-            ) // Set the alignment of the Text
-            .with_text_alignment(TextAlignment::TOP_CENTER)
-            // Set the style of the TextBundle itself.
-            .with_style(Style {
-                align_self: AlignSelf::FlexEnd,
-                position_type: PositionType::Absolute,
-                position: UiRect {
-                    top: Val::Percent(10.),
-                    left: Val::Percent(20.0),
-                    ..default()
-                },
-                ..default()
-            }),
-        )
-        .insert(PauseText);
-
-    commands
-        .spawn_bundle(
-            // Create a TextBundle that has a Text with a single section.
-            TextBundle::from_section(
-                // Accepts a `String` or any type that converts into a `String`, such as `&str`
-                "Press (Space) to resume",
-                TextStyle {
-                    font: asset_server.load(FONT_ASSET_PATH),
-                    font_size: HEALTH_FONT_SIZE,
-                    color: Color::WHITE,
-                },
-            ) // Set the alignment of the Text
-            .with_text_alignment(TextAlignment::TOP_CENTER)
-            // Set the style of the TextBundle itself.
-            .with_style(Style {
-                align_self: AlignSelf::FlexEnd,
-                position_type: PositionType::Absolute,
-                position: UiRect {
-                    top: Val::Percent(35.),
-                    left: Val::Percent(35.0),
-                    ..default()
-                },
-                ..default()
-            }),
-        )
-        .insert(PauseText)
-        .insert(ResumeText);
-
     commands
         .spawn_bundle(
             // Create a TextBundle that has a Text with a single section.
@@ -413,34 +429,8 @@ fn setup_menu_ui(mut commands: &mut Commands, asset_server: &mut ResMut<AssetSer
                 ..default()
             }),
         )
-        .insert(MenuText);
-
-    commands
-        .spawn_bundle(
-            // Create a TextBundle that has a Text with a single section.
-            TextBundle::from_section(
-                // Accepts a `String` or any type that converts into a `String`, such as `&str`
-                "Press Space to start",
-                TextStyle {
-                    font: asset_server.load(FONT_ASSET_PATH),
-                    font_size: HEALTH_FONT_SIZE - 10.,
-                    color: Color::WHITE,
-                },
-            ) // Set the alignment of the Text
-            .with_text_alignment(TextAlignment::TOP_CENTER)
-            // Set the style of the TextBundle itself.
-            .with_style(Style {
-                align_self: AlignSelf::FlexEnd,
-                position_type: PositionType::Absolute,
-                position: UiRect {
-                    top: Val::Percent(60.),
-                    left: Val::Percent(44.0),
-                    ..default()
-                },
-                ..default()
-            }),
-        )
-        .insert(MenuText);
+        .insert(MenuText)
+        .insert(PaletteText(ThemedKind::Enemy));
 }
 
 fn setup_lose_ui(mut commands: &mut Commands, asset_server: &mut ResMut<AssetServer>) {
@@ -497,46 +487,20 @@ fn setup_lose_ui(mut commands: &mut Commands, asset_server: &mut ResMut<AssetSer
             }),
         )
         .insert(LoseScoreText)
-        .insert(LoseText);
-
-    commands
-        .spawn_bundle(
-            // Create a TextBundle that has a Text with a single section.
-            TextBundle::from_section(
-                // Accepts a `String` or any type that converts into a `String`, such as `&str`
-                "Press Space to go again!",
-                TextStyle {
-                    font: asset_server.load(FONT_ASSET_PATH),
-                    font_size: HEALTH_FONT_SIZE - 10.,
-                    color: Color::WHITE,
-                },
-            ) // Set the alignment of the Text
-            .with_text_alignment(TextAlignment::TOP_CENTER)
-            // Set the style of the TextBundle itself.
-            .with_style(Style {
-                align_self: AlignSelf::FlexEnd,
-                position_type: PositionType::Absolute,
-                position: UiRect {
-                    top: Val::Percent(60.),
-                    left: Val::Percent(50.0),
-                    ..default()
-                },
-                ..default()
-            }),
-        )
+        .insert(PaletteText(ThemedKind::Player))
         .insert(LoseText);
 }
 
 fn update_ui(
     score: Res<Score>,
     game_state: Res<GameStateInfo>,
-    player_stats: Res<PlayerStats>,
+    menu_info: Res<MenuInfo>,
+    settings: Res<GameSettings>,
+    daily: Res<DailyChallenge>,
     mut playing_text_query: Query<
         (
             &mut Text,
             &mut Visibility,
-            Option<&HealthText>,
-            Option<&PowerupText>,
             Option<&RunText>,
             Option<&ScoreText>,
         ),
@@ -550,36 +514,22 @@ fn update_ui(
         (&mut Text, &mut Visibility, Option<&LoseScoreText>),
         (With<LoseText>, Without<PlayingText>, Without<MenuText>),
     >,
-    mut pause_text_query: Query<(&mut Text, &mut Visibility, Option<&ResumeText>),
-        (
-            With<PauseText>,
-            Without<PlayingText>,
-            Without<MenuText>,
-            Without<LoseText>,
-        ),
-    >,
 ) {
-    for (mut text, mut visibility, health_text, powerup_text, run_text, score_text) in
+    for (mut text, mut visibility, run_text, score_text) in
         playing_text_query.iter_mut()
     {
         match game_state.game_state {
             GamePlayState::Menu => {
                 visibility.is_visible = false;
             }
-            GamePlayState::Pause => {
+            GamePlayState::Pause | GamePlayState::Upgrade => {
                 visibility.is_visible = false;
             }
-            GamePlayState::Lose => {
+            GamePlayState::Lose | GamePlayState::Win => {
                 visibility.is_visible = false;
             }
             GamePlayState::Playing => {
                 visibility.is_visible = true;
-                if let Some(text_comp) = health_text {
-                    text.sections[0].value = format!("{}", player_stats.health);
-                }
-                if let Some(text_comp) = powerup_text {
-                    text.sections[0].value = format!("{}", player_stats.time_left_in_kill_mode);
-                }
                 if let Some(text_comp) = run_text {
                     if score.score >= 3. {
                         visibility.is_visible = false;
@@ -596,12 +546,13 @@ fn update_ui(
     for (mut text, mut visibility) in menu_text_query.iter_mut() {
         match game_state.game_state {
             GamePlayState::Menu => {
-                visibility.is_visible = true;
+                // the title only belongs on the front page, not settings or high scores
+                visibility.is_visible = menu_info.page == MenuPage::Main;
             }
-            GamePlayState::Pause => {
+            GamePlayState::Pause | GamePlayState::Upgrade => {
                 visibility.is_visible = false;
             }
-            GamePlayState::Lose => {
+            GamePlayState::Lose | GamePlayState::Win => {
                 visibility.is_visible = false;
             }
             GamePlayState::Playing => {
@@ -615,40 +566,19 @@ fn update_ui(
             GamePlayState::Menu => {
                 visibility.is_visible = false;
             }
-            GamePlayState::Pause => {
+            GamePlayState::Pause | GamePlayState::Upgrade => {
                 visibility.is_visible = false;
             }
-            GamePlayState::Lose => {
+            GamePlayState::Lose | GamePlayState::Win => {
                 visibility.is_visible = true;
                 if let Some(text_comp) = lose_score_text {
                     text.sections[0].value = format!("{}", score.score);
                     info!(score.score);
-                }
-            }
-            GamePlayState::Playing => {
-                visibility.is_visible = false;
-            }
-        }
-    }
-
-    for (mut text, mut visibility, resume_text) in pause_text_query.iter_mut() {
-        match game_state.game_state {
-            GamePlayState::Menu => {
-                visibility.is_visible = true;
-
-                if let Some(text_comp) = resume_text {
-                    visibility.is_visible = false;
-                }
-
-            }
-            GamePlayState::Pause => {
-                visibility.is_visible = true;
-            }
-            GamePlayState::Lose => {
-                visibility.is_visible = true;
-
-                if let Some(text_comp) = resume_text {
-                    visibility.is_visible = false;
+                } else if game_state.game_state == GamePlayState::Win {
+                    let mode = GameMode::current(&settings, &daily);
+                    text.sections[0].value = String::from(mode.win_text());
+                } else {
+                    text.sections[0].value = String::from("FINAL SCORE:");
                 }
             }
             GamePlayState::Playing => {
@@ -658,12 +588,11 @@ fn update_ui(
     }
 }
 
-fn setup_player(mut commands: Commands) {
-    commands.spawn_bundle(definitions_units::PlayerBundle::new());
+fn setup_player(mut commands: Commands, settings: Res<GameSettings>) {
+    players::spawn_players(&mut commands, settings.player_count);
 }
 
 fn game_tick_manager(
-    time: Res<Time>,
     mut game_tick_time: ResMut<GameTickInfo>,
     mut game_state: ResMut<GameStateInfo>,
     mut event_writer: EventWriter<TickEvent>,
@@ -672,162 +601,254 @@ fn game_tick_manager(
         GamePlayState::Menu => {}
         GamePlayState::Pause => {}
         GamePlayState::Lose => {}
+        GamePlayState::Win => {}
+        GamePlayState::Upgrade => {}
         GamePlayState::Playing => {
-            game_tick_time.time_till_next_tick += time.delta().as_secs_f32();
+            game_tick_time.run_time += TIME_STEP;
+            game_tick_time.time_till_next_tick += TIME_STEP;
             if game_tick_time.time_till_next_tick >= game_tick_time.time_between_ticks {
                 game_tick_time.time_till_next_tick -= game_tick_time.time_between_ticks;
+                game_tick_time.ticks += 1;
                 event_writer.send(default());
             }
         }
     }
 }
 
-fn player_menu_controls(
-    keyboard_input: Res<Input<KeyCode>>,
+/// Physics only moves while a run is being played, and in a network session only on the frames that
+/// simulate a step.
+fn pause_physics(
+    game_state: Res<GameStateInfo>,
+    frame_controls: Res<FrameControls>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    rapier_config.physics_pipeline_active =
+        game_state.game_state == GamePlayState::Playing && frame_controls.simulate;
+}
 
-    mut player_entity: Query<
-        (Entity, &mut Transform, &mut Velocity),
-        (With<Player>, Without<Enemy>),
+fn handle_menu_actions(
+    mut menu_actions: EventReader<MenuActionEvent>,
+    mut menu_info: ResMut<MenuInfo>,
+    mut settings: ResMut<GameSettings>,
+
+    mut run_entities: Query<
+        Entity,
+        Or<(
+            With<Player>,
+            With<Enemy>,
+            With<Boss>,
+            With<Health>,
+            With<Powerup>,
+        )>,
     >,
-    mut enemy_velocity: Query<(Entity), (With<(Enemy)>, Without<Player>)>,
-    mut health_entity: Query<(Entity), With<(Health)>>,
-    mut powerup_entity: Query<(Entity), With<(Powerup)>>,
 
-    mut player_stats: ResMut<PlayerStats>,
     mut game_state: ResMut<GameStateInfo>,
     mut event_writer: EventWriter<GamePlayState>,
     mut game_tick_time: ResMut<GameTickInfo>,
     mut commands: Commands,
     mut score: ResMut<Score>,
+    mut spawn_rng: ResMut<SpawnRng>,
+    mut net: ResMut<NetState>,
+    mut daily: ResMut<DailyChallenge>,
+    mut config: ResMut<GameConfig>,
     mut audio: Res<Audio>,
     mut asset_server: ResMut<AssetServer>,
     mut exit: EventWriter<AppExit>,
 ) {
-    match game_state.game_state {
-        GamePlayState::Menu => {
-            if keyboard_input.pressed(KeyCode::Space) {
+    for action in menu_actions.iter() {
+        match action.0 {
+            // network runs are seeded by the host, they can't follow the daily seed
+            MenuAction::StartDaily if net.is_online() => {}
+            MenuAction::Start | MenuAction::StartDaily => {
+                if action.0 == MenuAction::StartDaily {
+                    daily.date = Some(DailyDate::today());
+                    *config = GameConfig::defaults();
+                } else {
+                    daily.date = None;
+                    *config = GameConfig::load();
+                }
+                // the player count may have changed in the settings since the players were spawned
+                restart_game(
+                    &run_entities,
+                    &settings,
+                    &daily,
+                    &mut game_tick_time,
+                    &mut spawn_rng,
+                    &mut commands,
+                    &mut score,
+                );
+                net.start_run(RunSetup::new(&config, &settings));
                 game_state.change_game_play_state(GamePlayState::Playing, &mut event_writer);
                 game_tick_time.do_tick = true;
-                play_sound(&mut asset_server, &mut audio, AudioType::GameStart);
+                play_sound(&mut asset_server, &mut audio, &settings, AudioType::GameStart);
                 info!("Game Started");
             }
-
-            if keyboard_input.just_pressed(KeyCode::Escape) {
-                exit.send(AppExit);
+            MenuAction::OpenSettings => {
+                menu_info.page = MenuPage::Settings;
             }
-        }
-        GamePlayState::Pause => {
-            if keyboard_input.just_pressed(KeyCode::Escape) {
+            MenuAction::OpenHighScores => {
+                menu_info.page = MenuPage::HighScores;
+            }
+            MenuAction::OpenShop => {
+                menu_info.page = MenuPage::Shop;
+            }
+            MenuAction::Back => {
+                menu_info.page = MenuPage::Main;
+            }
+            MenuAction::Quit => {
                 exit.send(AppExit);
             }
-
-            if keyboard_input.pressed(KeyCode::Space) {
+            MenuAction::Pause => {
+                game_state.change_game_play_state(GamePlayState::Pause, &mut event_writer);
+            }
+            MenuAction::Resume => {
                 game_state.change_game_play_state(GamePlayState::Playing, &mut event_writer);
-                game_tick_time.do_tick = true;
-                play_sound(&mut asset_server, &mut audio, AudioType::GameStart);
-                info!("Game Started");
             }
-        }
-        GamePlayState::Lose => {
-            if keyboard_input.pressed(KeyCode::Space) {
+            MenuAction::Restart => {
+                // a daily run restarted after midnight moves on to the new day
+                if daily.date.is_some() {
+                    daily.date = Some(DailyDate::today());
+                }
                 restart_game(
-                    player_entity,
-                    enemy_velocity,
-                    health_entity,
-                    powerup_entity,
-                    player_stats,
-                    game_state,
-                    event_writer,
-                    game_tick_time,
-                    commands,
-                    score,
+                    &run_entities,
+                    &settings,
+                    &daily,
+                    &mut game_tick_time,
+                    &mut spawn_rng,
+                    &mut commands,
+                    &mut score,
                 );
-                play_sound(&mut asset_server, &mut audio, AudioType::GameStart);
+                net.start_run(RunSetup::new(&config, &settings));
+                game_state.change_game_play_state(GamePlayState::Playing, &mut event_writer);
+                play_sound(&mut asset_server, &mut audio, &settings, AudioType::GameStart);
                 info!("Game Started");
             }
-
-            if keyboard_input.just_pressed(KeyCode::Escape) {
-                exit.send(AppExit);
+            MenuAction::MainMenu => {
+                restart_game(
+                    &run_entities,
+                    &settings,
+                    &daily,
+                    &mut game_tick_time,
+                    &mut spawn_rng,
+                    &mut commands,
+                    &mut score,
+                );
+                menu_info.page = MenuPage::Main;
+                game_state.change_game_play_state(GamePlayState::Menu, &mut event_writer);
+            }
+            // network sessions always have one player on each side
+            MenuAction::CycleSetting(SettingKind::Players) if net.is_online() => {}
+            // the joining peer plays with the host's settings, see `net::RunSetup`
+            MenuAction::CycleSetting(
+                SettingKind::Mode | SettingKind::FlightModel | SettingKind::Weapon,
+            ) if net.is_joined() => {}
+            MenuAction::CycleSetting(setting) => {
+                settings.cycle(setting);
             }
+            MenuAction::ChooseUpgrade(_) => {} // see upgrades::handle_upgrade_choice
+            MenuAction::ShopItem(_) => {}      // see profile::handle_shop_actions
         }
-
-        GamePlayState::Playing => {}
     }
 }
 
 fn player_movement(
-    keyboard_input: Res<Input<KeyCode>>,
+    frame_controls: Res<FrameControls>,
+    settings: Res<GameSettings>,
+    daily: Res<DailyChallenge>,
+    config: Res<GameConfig>,
     mut game_tick_time: ResMut<GameTickInfo>,
-    mut game_state: ResMut<GameStateInfo>,
-    mut event_writer: EventWriter<GamePlayState>,
+    game_state: Res<GameStateInfo>,
 
-    mut player_stats: ResMut<PlayerStats>,
-    mut player_input: ResMut<PlayerInput>,
-    mut player_velocity: Query<(&mut Velocity, &Transform), With<definitions_units::Player>>,
-    mut camera: Query<
-        &mut Transform,
-        (
-            With<Camera>,
-            With<definitions_units::PlayerCam>,
-            Without<definitions_units::Player>,
-        ),
-    >,
+    mut players: Query<(
+        &Player,
+        &mut PlayerStats,
+        &mut PlayerInput,
+        &mut Velocity,
+        &mut Damping,
+        &Transform,
+    )>,
 ) {
-    let (mut velocity, transform) = player_velocity.single_mut();
-    let (mut cam_transform) = camera.single_mut();
-
-    match game_state.game_state {
-        GamePlayState::Menu => {} //implemented in different function for clarity
-        GamePlayState::Pause => {}
-        GamePlayState::Lose => {} //implemented in different function for clarity
-        GamePlayState::Playing => {
-            if keyboard_input.just_released(KeyCode::W) {
-                player_input.is_holding_forward = false;
-                player_stats.current_speed = Vec3::ZERO;
-            }
+    let flight_model = FlightModel::current(&settings, &daily);
+    for (player, mut player_stats, mut player_input, mut velocity, mut damping, transform) in
+        players.iter_mut()
+    {
+        let flight_damping = flight_model.damping();
+        if *damping != flight_damping {
+            *damping = flight_damping;
+        }
+        let controls = frame_controls.players[player.id];
 
-            if keyboard_input.just_released(KeyCode::A) || keyboard_input.just_released(KeyCode::D)
-            {
-                player_input.is_holding_turn = false;
+        match game_state.game_state {
+            GamePlayState::Menu => {} //implemented in different function for clarity
+            GamePlayState::Pause => {}
+            GamePlayState::Lose => {} //implemented in different function for clarity
+            GamePlayState::Win => {}
+            GamePlayState::Upgrade => {}
+            GamePlayState::Playing if !frame_controls.simulate => {} // waiting on a network peer
+            GamePlayState::Playing if flight_model == FlightModel::Momentum => {
+                player_input.is_holding_forward = controls.forward;
+                player_input.is_holding_turn = controls.turning();
+                let mut turn = 0.;
+                if controls.turn_left {
+                    turn += 1.;
+                }
+                if controls.turn_right {
+                    turn -= 1.;
+                }
+                fly_momentum(
+                    controls.forward,
+                    turn,
+                    TIME_STEP,
+                    &player_stats,
+                    &config,
+                    transform,
+                    &mut velocity,
+                );
             }
+            GamePlayState::Playing => {
+                if !controls.forward && player_input.is_holding_forward {
+                    player_input.is_holding_forward = false;
+                    player_stats.current_speed = Vec3::ZERO;
+                }
 
-            if keyboard_input.pressed(KeyCode::W) {
-                player_input.is_holding_forward = true;
-                let speed_per_frame = player_stats.speed_per_frame;
-                player_stats.add_forward_speed(speed_per_frame);
-                let rotated_velocity = transform.rotation * (player_stats.current_speed);
-                velocity.linvel = rotated_velocity.truncate();
-            }
-            if keyboard_input.pressed(KeyCode::A) {
-                velocity.angvel = 1. * 5.;
-            }
-            if keyboard_input.pressed(KeyCode::D) {
-                velocity.angvel = -1. * 5.;
-            }
+                if !controls.turning() {
+                    player_input.is_holding_turn = false;
+                }
 
-            if keyboard_input.pressed(KeyCode::Escape) {
-                game_state.change_game_play_state(GamePlayState::Pause, &mut event_writer);
+                // thrust would cancel out dash and knockback impulses, so it waits for them to finish
+                if controls.forward && !player_stats.is_thrust_locked() {
+                    player_input.is_holding_forward = true;
+                    let speed_per_frame = player_stats.speed_per_frame;
+                    player_stats.add_forward_speed(speed_per_frame);
+                    let rotated_velocity = transform.rotation * (player_stats.current_speed);
+                    velocity.linvel = rotated_velocity.truncate();
+                }
+                if controls.turn_left {
+                    velocity.angvel = 1. * 5.;
+                }
+                if controls.turn_right {
+                    velocity.angvel = -1. * 5.;
+                }
             }
         }
     }
-
-    cam_transform.translation = transform.translation;
 }
 
 fn handle_tick_events(
-    mut player_sprite: Query<&mut Sprite, With<Player>>,
-
     mut spawn_event_writer: EventWriter<SpawnEvents>,
     mut tick_event_reader: EventReader<TickEvent>,
-    mut player_stats: ResMut<PlayerStats>,
-    mut player_health_event: EventWriter<HealthGone>,
+    players: Query<Entity, With<Player>>,
+    mut damage_events: EventWriter<DamageEvent>,
     mut score: ResMut<Score>,
 ) {
-    let mut player_sprite = player_sprite.single_mut();
-
     for tick in tick_event_reader.iter() {
-        player_stats.health_damage(1, &mut player_health_event);
-        player_stats.powerup_time_decrease(&mut player_sprite);
+        for player in players.iter() {
+            damage_events.send(DamageEvent {
+                target: player,
+                amount: 1,
+                source: DamageSource::Tick,
+            });
+        }
         spawn_event_writer.send(SpawnEvents(false));
         score.score += 1.;
     }
@@ -838,26 +859,36 @@ fn handle_spawn_events(
     mut enemy_spawner_resource: ResMut<Spawner>,
     mut commands: Commands,
     mut player_transform: Query<(&Transform), (With<Player>, Without<Enemy>)>,
+    mut spawn_rng: ResMut<SpawnRng>,
 
     mut spawn_events: EventReader<SpawnEvents>,
 ) {
     for event in spawn_events.iter() {
+        // every wave and pickup forms around one of the players, so nobody is left out
+        let player_transform = match player_transform.iter().choose(spawn_rng.rng()) {
+            Some(player_transform) => player_transform,
+            None => continue,
+        };
+
         Spawner::spawn_next_wave(
-            &mut player_transform,
+            player_transform,
             &game_tick_time,
             &enemy_spawner_resource,
+            &mut spawn_rng,
             &mut commands,
         );
 
         Spawner::spawn_health(
-            &mut player_transform,
+            player_transform,
             &enemy_spawner_resource,
+            &mut spawn_rng,
             &mut commands,
         );
 
         Spawner::spawn_powerup(
-            &mut player_transform,
+            player_transform,
             &enemy_spawner_resource,
+            &mut spawn_rng,
             &mut commands,
         );
     }
@@ -865,35 +896,94 @@ fn handle_spawn_events(
 
 fn handle_enemy_ai(
     mut commands: Commands,
-    mut player_velocity: Query<(&Transform), (With<Player>, Without<Enemy>)>,
+    players: Query<(&Transform, &PlayerStats, &Hitpoints), (With<Player>, Without<Enemy>)>,
     mut enemy_velocity: Query<
-        (Entity, &mut Velocity, &mut Transform),
+        (
+            Entity,
+            &mut Velocity,
+            &mut Transform,
+            Option<&mut Staggered>,
+            Option<&mut RangedEnemy>,
+        ),
         (With<(Enemy)>, Without<Player>),
     >,
+    mut projectile_events: EventWriter<ProjectileEvent>,
     mut game_state: ResMut<GameStateInfo>,
 ) {
     if game_state.game_state != GamePlayState::Playing {
         return;
     }
-    let (player_transform) = player_velocity.single_mut();
+    let living_players = || {
+        players
+            .iter()
+            .filter(|(_, _, hitpoints)| !hitpoints.is_dead())
+            .map(|(transform, _, _)| transform.translation.truncate())
+    };
     let mut enemy_count = 0;
-    for (entity, mut velocity, mut transform) in enemy_velocity.iter_mut() {
-        let distance_to_player = player_transform.translation - transform.translation;
+    // any player's time slow slows enemies down for everyone
+    let speed_multiplier = players
+        .iter()
+        .map(|(_, player_stats, _)| player_stats.powerups.enemy_speed_multiplier())
+        .fold(1., f32::min);
+    let enemy_speed = 200.0 * speed_multiplier;
+    for (entity, mut velocity, mut transform, staggered, ranged) in enemy_velocity.iter_mut() {
+        // chase whoever is closest
+        let player_position =
+            match players::nearest(transform.translation.truncate(), living_players()) {
+                Some(player_position) => player_position,
+                None => return,
+            };
+        let distance_to_player = player_position.extend(0.) - transform.translation;
 
         if distance_to_player.x > MAX_OBJECT_DISTANCE || distance_to_player.y > MAX_OBJECT_DISTANCE
         {
-            commands.entity(entity).despawn();
+            commands.entity(entity).despawn_recursive();
+        }
+
+        // let knockback play out before chasing again
+        if let Some(mut staggered) = staggered {
+            if staggered.time_left > 0. {
+                staggered.time_left -= TIME_STEP;
+                continue;
+            }
         }
 
         let mut angle = f32::atan2(
-            player_transform.translation.y - transform.translation.y,
-            player_transform.translation.x - transform.translation.x,
+            player_position.y - transform.translation.y,
+            player_position.x - transform.translation.x,
         );
 
         transform.rotation = Quat::from_rotation_z(angle);
+
+        // ranged enemies hold a distance and shoot, the rest chase
+        if let Some(mut ranged) = ranged {
+            let distance = distance_to_player.truncate().length();
+            let direction = distance_to_player.truncate().normalize_or_zero();
+            let approach = if distance < RANGED_ENEMY_MIN_DISTANCE {
+                -1.
+            } else if distance > RANGED_ENEMY_MAX_DISTANCE {
+                1.
+            } else {
+                0.
+            };
+            velocity.linvel = direction * enemy_speed * approach;
+
+            ranged.fire_cooldown_left -= TIME_STEP;
+            if ranged.fire_cooldown_left <= 0. && distance <= RANGED_ENEMY_MAX_DISTANCE * 1.5 {
+                ranged.fire_cooldown_left = RANGED_ENEMY_FIRE_INTERVAL;
+                projectile_events.send(ProjectileEvent {
+                    position: transform.translation.truncate() + direction * transform.scale.x,
+                    velocity: direction * RANGED_ENEMY_PROJECTILE_SPEED * speed_multiplier,
+                    team: ProjectileTeam::Enemy,
+                });
+            }
+            enemy_count += 1;
+            continue;
+        }
+
         let rotated_velocity = transform.rotation
             * Vec3 {
-                x: 200.0,
+                x: enemy_speed,
                 y: 0.0,
                 z: 0.0,
             };
@@ -905,138 +995,395 @@ fn handle_enemy_ai(
 }
 
 fn handle_player_colliding(
-    mut active_events: EventReader<CollisionEvent>,
-    player: Query<&CollidingEntities, With<Player>>,
-    mut player_sprite: Query<&mut Sprite, With<Player>>,
-    mut enemy_entity: Query<(&Enemy)>,
-    mut health_entity: Query<(&Health)>,
-    mut powerup_entity: Query<(&Powerup)>,
-    mut player_stats: ResMut<PlayerStats>,
-    mut health_event: EventWriter<HealthGone>,
+    mut player: Query<
+        (
+            Entity,
+            &CollidingEntities,
+            &Transform,
+            &ReadMassProperties,
+            &mut Hitpoints,
+            &mut PlayerStats,
+        ),
+        With<Player>,
+    >,
+    mut enemy_entity: Query<(&Enemy, &Transform, &Hitpoints), Without<Player>>,
+    mut health_entity: Query<(&Health, &Transform)>,
+    mut powerup_entity: Query<(&Powerup, &Transform)>,
+    mut projectiles: Query<(&mut Projectile, &Transform)>,
+    shockwave_targets: Query<(Entity, &Transform, &ReadMassProperties), With<Enemy>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut popup_events: EventWriter<PopupEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
     mut commands: Commands,
-    mut score: ResMut<Score>,
+    settings: Res<GameSettings>,
+    config: Res<GameConfig>,
+    game_state: Res<GameStateInfo>,
     mut audio: Res<Audio>,
     mut asset_server: ResMut<AssetServer>,
 ) {
-    let mut player_sprite = player_sprite.single_mut();
-
-    for player in player.iter() {
-        for collision in player.iter() {
-            if let Ok(health) = health_entity.get(collision) {
-                player_stats.health_heal_up_to_ten();
-                play_sound(&mut asset_server, &mut audio, AudioType::HealthPickup);
-                commands.entity(collision).despawn();
+    // contacts from before a pause still show up, they shouldn't hurt anyone until the run goes on
+    if game_state.game_state != GamePlayState::Playing {
+        return;
+    }
+    let palette = settings.palette();
+    // with several players two of them can touch the same thing in one frame, only the first gets it
+    let mut used_up: Vec<Entity> = Vec::new();
+    for (_, colliding_entities, _, _, mut hitpoints, _) in player.iter_mut() {
+        for collision in colliding_entities.iter() {
+            if used_up.contains(&collision) {
+                continue;
+            }
+            if let Ok((health, transform)) = health_entity.get(collision) {
+                used_up.push(collision);
+                hitpoints.heal(HEALTH_PICKUP_HEAL);
+                popup_events.send(PopupEvent {
+                    position: transform.translation.truncate(),
+                    text: format!("+{}", HEALTH_PICKUP_HEAL),
+                    color: palette.health,
+                });
+                particle_events.send(ParticleEvent {
+                    effect: ParticleEffect::Pickup(palette.health),
+                    position: transform.translation.truncate(),
+                    direction: Vec2::ZERO,
+                });
+                play_sound(&mut asset_server, &mut audio, &settings, AudioType::HealthPickup);
+                commands.entity(collision).despawn_recursive();
             }
         }
     }
 
-    for player in player.iter() {
-        for collision in player.iter() {
-            if let Ok(enemy) = enemy_entity.get(collision) {
-                if player_stats.kill_mode {
-                    score.score += 5.;
-                    play_sound(
-                        &mut asset_server,
-                        &mut audio,
-                        AudioType::EnemyCollisionKillMode,
-                    );
+    for (player_entity, colliding_entities, player_transform, player_mass, _, mut player_stats) in
+        player.iter_mut()
+    {
+        for collision in colliding_entities.iter() {
+            if used_up.contains(&collision) {
+                continue;
+            }
+            // enemies and their projectiles hurt the same way, kill mode destroys both
+            let (position, enemy_hitpoints, damage) =
+                if let Ok((_, transform, enemy_hitpoints)) = enemy_entity.get(collision) {
+                    (
+                        transform.translation,
+                        Some(enemy_hitpoints.current),
+                        config.enemy_contact_damage,
+                    )
+                } else if let Ok((projectile, transform)) = projectiles.get(collision) {
+                    if !projectile.is_hostile() {
+                        continue;
+                    }
+                    (transform.translation, None, config.projectile_damage)
                 } else {
-                    player_stats.health_damage(2, &mut health_event);
-                    play_sound(
-                        &mut asset_server,
-                        &mut audio,
-                        AudioType::EnemyCollisionDamage,
+                    continue;
+                };
+            if player_stats.kill_mode {
+                used_up.push(collision);
+                match enemy_hitpoints {
+                    // kill mode kills outright, handle_enemy_deaths cleans the enemy up
+                    Some(enemy_hitpoints) => damage_events.send(DamageEvent {
+                        target: collision,
+                        amount: enemy_hitpoints,
+                        source: DamageSource::Contact(player_entity),
+                    }),
+                    None => {
+                        destroy_projectile(collision, &mut projectiles);
+                        particle_events.send(ParticleEvent {
+                            effect: ParticleEffect::EnemyDeath,
+                            position: position.truncate(),
+                            direction: Vec2::ZERO,
+                        });
+                    }
+                }
+                continue;
+            } else if player_stats.is_invulnerable() {
+                // leave the enemy be, it can still hit once the invulnerability wears off
+                continue;
+            } else if player_stats.powerups.absorb_hit() {
+                popup_events.send(PopupEvent {
+                    position: position.truncate(),
+                    text: String::from("BLOCKED"),
+                    color: palette.powerup,
+                });
+                particle_events.send(ParticleEvent {
+                    effect: ParticleEffect::EnemyDeath,
+                    position: position.truncate(),
+                    direction: Vec2::ZERO,
+                });
+                play_sound(
+                    &mut asset_server,
+                    &mut audio,
+                    &settings,
+                    AudioType::EnemyCollisionKillMode,
+                );
+            } else {
+                damage_events.send(DamageEvent {
+                    target: player_entity,
+                    amount: damage,
+                    source: DamageSource::Contact(collision),
+                });
+                player_stats.invulnerable_time_left = config.hit_invulnerable_time;
+                player_stats.knockback_time_left = config.hit_knockback_time;
+                let away = (player_transform.translation - position)
+                    .truncate()
+                    .normalize_or_zero();
+                commands.entity(player_entity).insert(ExternalImpulse {
+                    impulse: away * config.hit_knockback_speed * player_mass.0.mass,
+                    torque_impulse: 0.,
+                });
+                popup_events.send(PopupEvent {
+                    position: position.truncate(),
+                    text: format!("-{}", damage),
+                    color: palette.enemy,
+                });
+                particle_events.send(ParticleEvent {
+                    effect: ParticleEffect::PlayerDamage,
+                    position: position.truncate(),
+                    direction: Vec2::ZERO,
+                });
+                play_sound(
+                    &mut asset_server,
+                    &mut audio,
+                    &settings,
+                    AudioType::EnemyCollisionDamage,
+                );
+            }
+            used_up.push(collision);
+            if enemy_hitpoints.is_some() {
+                commands.entity(collision).despawn_recursive();
+            } else {
+                destroy_projectile(collision, &mut projectiles);
+            }
+        }
+    }
+    for (_, colliding_entities, _, _, _, mut player_stats) in player.iter_mut() {
+        for collision in colliding_entities.iter() {
+            if used_up.contains(&collision) {
+                continue;
+            }
+            if let Ok((powerup, transform)) = powerup_entity.get(collision) {
+                used_up.push(collision);
+                let kind = powerup.0;
+                play_sound(
+                    &mut asset_server,
+                    &mut audio,
+                    &settings,
+                    AudioType::PowerupPickup(kind),
+                );
+                popup_events.send(PopupEvent {
+                    position: transform.translation.truncate(),
+                    text: String::from(kind.pickup_text()),
+                    color: palette.powerup,
+                });
+                particle_events.send(ParticleEvent {
+                    effect: ParticleEffect::Pickup(palette.powerup),
+                    position: transform.translation.truncate(),
+                    direction: Vec2::ZERO,
+                });
+                player_stats.activate_powerup(kind);
+                if kind == PowerupKind::Shockwave {
+                    shockwave(
+                        transform.translation.truncate(),
+                        &mut commands,
+                        &shockwave_targets,
                     );
                 }
-                commands.entity(collision).despawn();
+                commands.entity(collision).despawn_recursive();
             }
         }
     }
-    for player in player.iter() {
-        for collision in player.iter() {
-            if let Ok(powerup) = powerup_entity.get(collision) {
-                play_sound(&mut asset_server, &mut audio, AudioType::PowerupPickup);
-                player_stats.health_heal_up_to_ten();
-                player_stats.powerup_mode(&mut player_sprite);
-                commands.entity(collision).despawn();
+}
+
+fn destroy_projectile(entity: Entity, projectiles: &mut Query<(&mut Projectile, &Transform)>) {
+    if let Ok((mut projectile, _)) = projectiles.get_mut(entity) {
+        projectile.destroy();
+    }
+}
+
+/// Enemies killed by a player, by contact in kill mode or by their shots, are worth score. Anything
+/// else that kills them only clears them away.
+fn handle_enemy_deaths(
+    mut death_events: EventReader<DeathEvent>,
+    enemies: Query<&Transform, With<Enemy>>,
+    players: Query<Entity, With<Player>>,
+    mut popup_events: EventWriter<PopupEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+    mut commands: Commands,
+    mut score: ResMut<Score>,
+    settings: Res<GameSettings>,
+    mut audio: Res<Audio>,
+    mut asset_server: ResMut<AssetServer>,
+) {
+    for event in death_events.iter() {
+        let transform = match enemies.get(event.entity) {
+            Ok(transform) => transform,
+            Err(_) => continue,
+        };
+        let killed_by_player = match event.source {
+            DamageSource::Contact(entity) | DamageSource::Projectile(entity) => {
+                players.get(entity).is_ok()
             }
+            DamageSource::Tick => false,
+        };
+        if killed_by_player {
+            score.score += 5.;
+            popup_events.send(PopupEvent {
+                position: transform.translation.truncate(),
+                text: String::from("+5"),
+                color: SCORE_COLOR,
+            });
         }
+        particle_events.send(ParticleEvent {
+            effect: ParticleEffect::EnemyDeath,
+            position: transform.translation.truncate(),
+            direction: Vec2::ZERO,
+        });
+        play_sound(
+            &mut asset_server,
+            &mut audio,
+            &settings,
+            AudioType::EnemyCollisionKillMode,
+        );
+        commands.entity(event.entity).despawn_recursive();
     }
 }
 
+/// A player who runs out of health drops out of the run while anyone else is still alive, the run is
+/// lost once the last player dies.
 fn handle_player_death(
-    mut health_event: EventReader<HealthGone>,
+    mut death_events: EventReader<DeathEvent>,
+    players: Query<(Entity, &Transform, &Hitpoints), With<Player>>,
+    mut particle_events: EventWriter<ParticleEvent>,
+    mut commands: Commands,
     mut game_state: ResMut<GameStateInfo>,
     mut event_writer: EventWriter<GamePlayState>,
+    mut high_scores: ResMut<HighScores>,
+    mut daily_results: ResMut<DailyResults>,
+    daily: Res<DailyChallenge>,
+    score: Res<Score>,
+    settings: Res<GameSettings>,
     mut audio: Res<Audio>,
     mut asset_server: ResMut<AssetServer>,
 ) {
-    for event in health_event.iter() {
+    for event in death_events.iter() {
+        let transform = match players.get(event.entity) {
+            Ok((_, transform, _)) => transform,
+            Err(_) => continue,
+        };
+        if game_state.game_state != GamePlayState::Playing {
+            continue;
+        }
+        play_sound(&mut asset_server, &mut audio, &settings, AudioType::Death);
+
+        let anyone_alive = players
+            .iter()
+            .any(|(entity, _, hitpoints)| entity != event.entity && !hitpoints.is_dead());
+        if anyone_alive {
+            particle_events.send(ParticleEvent {
+                effect: ParticleEffect::PlayerDamage,
+                position: transform.translation.truncate(),
+                direction: Vec2::ZERO,
+            });
+            commands.entity(event.entity).despawn_recursive();
+            continue;
+        }
         game_state.change_game_play_state(GamePlayState::Lose, &mut event_writer);
-        play_sound(&mut asset_server, &mut audio, AudioType::Death);
+        // daily runs are only compared with each other, they go in their own history
+        match daily.date {
+            Some(date) => daily_results.submit(date, score.score),
+            None => {
+                high_scores.submit(GameMode::current(&settings, &daily), score.score);
+            }
+        }
     }
 }
 
-fn restart_game(
-    mut player_entity: Query<
-        (Entity, &mut Transform, &mut Velocity),
-        (With<Player>, Without<Enemy>),
-    >,
-    mut enemy_velocity: Query<(Entity), (With<(Enemy)>, Without<Player>)>,
-    mut health_entity: Query<(Entity), With<(Health)>>,
-    mut powerup_entity: Query<(Entity), With<(Powerup)>>,
-    mut player_stats: ResMut<PlayerStats>,
+/// Ends the run with a win once the goal of its game mode is reached. Losing is left to
+/// `handle_player_death`, which every mode shares.
+fn handle_mode_goal(
+    game_tick_time: Res<GameTickInfo>,
     mut game_state: ResMut<GameStateInfo>,
     mut event_writer: EventWriter<GamePlayState>,
-    mut game_tick_time: ResMut<GameTickInfo>,
-    mut commands: Commands,
-    mut score: ResMut<Score>,
+    mut high_scores: ResMut<HighScores>,
+    daily: Res<DailyChallenge>,
+    score: Res<Score>,
+    settings: Res<GameSettings>,
+    mut audio: Res<Audio>,
+    mut asset_server: ResMut<AssetServer>,
 ) {
-    for (entity) in enemy_velocity.iter_mut() {
-        commands.entity(entity).despawn();
+    if game_state.game_state != GamePlayState::Playing {
+        return;
     }
-
-    for entity in health_entity.iter_mut() {
-        commands.entity(entity).despawn();
+    let mode = GameMode::current(&settings, &daily);
+    if !mode.is_won(&game_tick_time) {
+        return;
     }
+    play_sound(&mut asset_server, &mut audio, &settings, AudioType::GameStart);
+    game_state.change_game_play_state(GamePlayState::Win, &mut event_writer);
+    high_scores.submit(mode, score.score);
+}
 
-    for entity in powerup_entity.iter_mut() {
-        commands.entity(entity).despawn();
+/// Clears out everything from the last run and spawns fresh players at the start. The caller decides
+/// which state the game goes to afterwards.
+fn restart_game(
+    run_entities: &Query<
+        Entity,
+        Or<(
+            With<Player>,
+            With<Enemy>,
+            With<Boss>,
+            With<Health>,
+            With<Powerup>,
+        )>,
+    >,
+    settings: &GameSettings,
+    daily: &DailyChallenge,
+    game_tick_time: &mut GameTickInfo,
+    spawn_rng: &mut SpawnRng,
+    commands: &mut Commands,
+    score: &mut Score,
+) {
+    // network sessions reseed from the shared seed once the run actually starts. Repeating the seed
+    // replays the same spawns, which is what makes racing a ghost fair
+    let seed = if let Some(date) = daily.date {
+        date.seed()
+    } else if settings.repeat_seed {
+        spawn_rng.seed
+    } else {
+        thread_rng().gen()
+    };
+    spawn_rng.reseed(seed);
+    for entity in run_entities.iter() {
+        commands.entity(entity).despawn_recursive();
     }
 
-    for (entity, mut transform, mut velocity) in player_entity.iter_mut() {
-        transform.translation = Vec3 {
-            x: 0.0,
-            y: 0.0,
-            z: 100.0,
-        };
-
-        velocity.linvel = Vec2 { x: 0.0, y: 0.0 };
-        velocity.angvel = 0.;
-    }
+    players::spawn_players(commands, players::player_count(settings, daily));
     score.score = 0.;
     game_tick_time.do_tick = true;
-    player_stats.health_heal_up_to_ten();
-    player_stats.time_left_in_kill_mode = 0.;
-    player_stats.kill_mode = false;
-    game_state.change_game_play_state(GamePlayState::Playing, &mut event_writer);
+    game_tick_time.time_till_next_tick = 0.;
+    game_tick_time.run_time = 0.;
+    game_tick_time.ticks = 0;
 }
 
 fn play_sound(
     asset_server: &mut ResMut<AssetServer>,
     audio: &mut Res<Audio>,
+    settings: &GameSettings,
     audio_type: AudioType,
 ) {
+    if !settings.sound_effects {
+        return;
+    }
+
     match audio_type {
         AudioType::HealthPickup => {
             audio
                 .play(asset_server.load(HEALTH_PICKUP_ASSET_PATH))
                 .with_volume(0.7);
         }
-        AudioType::PowerupPickup => {
+        AudioType::PowerupPickup(kind) => {
             audio
                 .play(asset_server.load(POWERUP_PICKUP_ASSET_PATH))
-                .with_volume(0.7);
+                .with_volume(0.7)
+                .with_playback_rate(kind.pickup_playback_rate());
         }
         AudioType::EnemyCollisionDamage => {
             audio
@@ -0,0 +1,480 @@
+use crate::daily::DailyResults;
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::high_scores::HighScores;
+use crate::profile::{Profile, SHOP};
+use crate::settings::{GameSettings, SettingKind};
+use crate::theme::{PaletteText, ThemedKind};
+use crate::upgrades::{choice_label, UpgradeOffer, OFFERED_UPGRADES};
+use crate::{ENEMY_COLOR, FONT_ASSET_PATH, HEALTH_FONT_SIZE, TEXT_COLOR};
+use bevy::prelude::*;
+
+const BUTTON_WIDTH: Val = Val::Px(380.);
+const BUTTON_HEIGHT: Val = Val::Px(56.);
+const BUTTON_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.08);
+const FOCUSED_BUTTON_COLOR: Color = Color::rgba(0.5, 0.5, 1.0, 0.45);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuPage {
+    Main,
+    Settings,
+    HighScores,
+    Shop,
+    Pause,
+    Lose,
+    Win,
+    Upgrade,
+}
+
+impl MenuPage {
+    // distance from the top of the screen the page's column of widgets starts at
+    fn top_percent(&self) -> f32 {
+        match self {
+            MenuPage::Main => 50.,
+            MenuPage::Settings => 15.,
+            MenuPage::HighScores => 15.,
+            MenuPage::Shop => 10.,
+            MenuPage::Pause => 30.,
+            MenuPage::Lose => 55.,
+            MenuPage::Win => 55.,
+            MenuPage::Upgrade => 30.,
+        }
+    }
+
+    fn title(&self) -> Option<&'static str> {
+        match self {
+            MenuPage::Main => None, // the main menu and lose screen keep their own big text
+            MenuPage::Settings => Some("SETTINGS"),
+            MenuPage::HighScores => Some("HIGH SCORES"),
+            MenuPage::Shop => Some("SHOP"),
+            MenuPage::Pause => Some("PAUSED"),
+            MenuPage::Lose => None,
+            MenuPage::Win => None,
+            MenuPage::Upgrade => Some("UPGRADE"),
+        }
+    }
+
+    fn buttons(&self) -> Vec<MenuAction> {
+        match self {
+            MenuPage::Main => vec![
+                MenuAction::Start,
+                MenuAction::StartDaily,
+                MenuAction::OpenSettings,
+                MenuAction::OpenHighScores,
+                MenuAction::OpenShop,
+                MenuAction::Quit,
+            ],
+            MenuPage::Settings => SettingKind::ALL
+                .iter()
+                .map(|setting| MenuAction::CycleSetting(*setting))
+                .chain(std::iter::once(MenuAction::Back))
+                .collect(),
+            MenuPage::HighScores => vec![MenuAction::Back],
+            MenuPage::Shop => (0..SHOP.len())
+                .map(MenuAction::ShopItem)
+                .chain(std::iter::once(MenuAction::Back))
+                .collect(),
+            MenuPage::Pause => vec![
+                MenuAction::Resume,
+                MenuAction::Restart,
+                MenuAction::MainMenu,
+            ],
+            MenuPage::Lose | MenuPage::Win => vec![MenuAction::Restart, MenuAction::MainMenu],
+            MenuPage::Upgrade => (0..OFFERED_UPGRADES)
+                .map(MenuAction::ChooseUpgrade)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuAction {
+    Start,
+    StartDaily,
+    OpenSettings,
+    OpenHighScores,
+    OpenShop,
+    Quit,
+    Pause,
+    Resume,
+    Restart,
+    MainMenu,
+    Back,
+    CycleSetting(SettingKind),
+    ChooseUpgrade(usize), // index into the upgrades on offer
+    ShopItem(usize),      // index into `SHOP`
+}
+
+impl MenuAction {
+    fn label(&self) -> &'static str {
+        match self {
+            MenuAction::Start => "Start",
+            MenuAction::StartDaily => "Daily Challenge",
+            MenuAction::OpenSettings => "Settings",
+            MenuAction::OpenHighScores => "High Scores",
+            MenuAction::OpenShop => "Shop",
+            MenuAction::Quit => "Quit",
+            MenuAction::Pause => "Pause",
+            MenuAction::Resume => "Resume",
+            MenuAction::Restart => "Restart",
+            MenuAction::MainMenu => "Main Menu",
+            MenuAction::Back => "Back",
+            MenuAction::CycleSetting(_) => "", // filled in from the settings every frame
+            MenuAction::ChooseUpgrade(_) => "", // filled in from the upgrades on offer
+            MenuAction::ShopItem(_) => "",     // filled in from the profile
+        }
+    }
+}
+
+/// Sent when a menu button gets activated by any input device.
+pub struct MenuActionEvent(pub MenuAction);
+
+pub struct MenuInfo {
+    pub page: MenuPage, // which page of the main menu is open while in GamePlayState::Menu
+    focused: usize,
+    shown_page: Option<MenuPage>,
+}
+
+impl FromWorld for MenuInfo {
+    fn from_world(_world: &mut World) -> Self {
+        MenuInfo {
+            page: MenuPage::Main,
+            focused: 0,
+            shown_page: None,
+        }
+    }
+}
+
+impl MenuInfo {
+    /// The page that should be on screen right now, None while playing.
+    pub fn current_page(&self, game_state: &GameStateInfo) -> Option<MenuPage> {
+        match game_state.game_state {
+            GamePlayState::Menu => Some(self.page),
+            GamePlayState::Pause => Some(MenuPage::Pause),
+            GamePlayState::Lose => Some(MenuPage::Lose),
+            GamePlayState::Win => Some(MenuPage::Win),
+            GamePlayState::Upgrade => Some(MenuPage::Upgrade),
+            GamePlayState::Playing => None,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct MenuWidget(MenuPage); // every node of a page, used to show and hide the page
+#[derive(Component)]
+pub struct MenuButton {
+    page: MenuPage,
+    index: usize,
+    action: MenuAction,
+}
+#[derive(Component)]
+pub struct MenuButtonText(MenuAction);
+#[derive(Component)]
+pub struct HighScoresText;
+#[derive(Component)]
+pub struct ShopCreditsText;
+
+pub fn setup_menus(commands: &mut Commands, asset_server: &mut ResMut<AssetServer>) {
+    let pages = [
+        MenuPage::Main,
+        MenuPage::Settings,
+        MenuPage::HighScores,
+        MenuPage::Shop,
+        MenuPage::Pause,
+        MenuPage::Lose,
+        MenuPage::Win,
+        MenuPage::Upgrade,
+    ];
+
+    for page in pages {
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Percent(page.top_percent()),
+                        left: Val::Percent(0.),
+                        ..default()
+                    },
+                    size: Size::new(Val::Percent(100.), Val::Auto),
+                    // column reverse goes from top to bottom
+                    flex_direction: FlexDirection::ColumnReverse,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                color: UiColor(Color::NONE),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(MenuWidget(page))
+            .with_children(|parent| {
+                if let Some(title) = page.title() {
+                    parent
+                        .spawn_bundle(
+                            TextBundle::from_section(
+                                title,
+                                TextStyle {
+                                    font: asset_server.load(FONT_ASSET_PATH),
+                                    font_size: HEALTH_FONT_SIZE + 40.,
+                                    color: ENEMY_COLOR,
+                                },
+                            )
+                            .with_style(Style {
+                                margin: UiRect::all(Val::Px(20.)),
+                                ..default()
+                            }),
+                        )
+                        .insert(MenuWidget(page))
+                        .insert(PaletteText(ThemedKind::Enemy));
+                }
+
+                if page == MenuPage::HighScores {
+                    parent
+                        .spawn_bundle(
+                            TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font: asset_server.load(FONT_ASSET_PATH),
+                                    font_size: HEALTH_FONT_SIZE - 10.,
+                                    color: Color::WHITE,
+                                },
+                            )
+                            .with_text_alignment(TextAlignment::TOP_CENTER)
+                            .with_style(Style {
+                                margin: UiRect::all(Val::Px(20.)),
+                                ..default()
+                            }),
+                        )
+                        .insert(HighScoresText)
+                        .insert(MenuWidget(page));
+                }
+
+                if page == MenuPage::Shop {
+                    parent
+                        .spawn_bundle(
+                            TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font: asset_server.load(FONT_ASSET_PATH),
+                                    font_size: HEALTH_FONT_SIZE - 10.,
+                                    color: Color::WHITE,
+                                },
+                            )
+                            .with_style(Style {
+                                margin: UiRect::all(Val::Px(10.)),
+                                ..default()
+                            }),
+                        )
+                        .insert(ShopCreditsText)
+                        .insert(MenuWidget(page));
+                }
+
+                for (index, action) in page.buttons().into_iter().enumerate() {
+                    parent
+                        .spawn_bundle(ButtonBundle {
+                            style: Style {
+                                size: Size::new(BUTTON_WIDTH, BUTTON_HEIGHT),
+                                margin: UiRect::all(Val::Px(6.)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            color: UiColor(BUTTON_COLOR),
+                            visibility: Visibility { is_visible: false },
+                            ..default()
+                        })
+                        .insert(MenuButton {
+                            page,
+                            index,
+                            action,
+                        })
+                        .insert(MenuWidget(page))
+                        .with_children(|parent| {
+                            parent
+                                .spawn_bundle(TextBundle::from_section(
+                                    action.label(),
+                                    TextStyle {
+                                        font: asset_server.load(FONT_ASSET_PATH),
+                                        font_size: HEALTH_FONT_SIZE - 10.,
+                                        color: Color::WHITE,
+                                    },
+                                ))
+                                .insert(MenuButtonText(action))
+                                .insert(MenuWidget(page));
+                        });
+                }
+            });
+    }
+}
+
+/// Moves the focus with keyboard, gamepad and mouse and sends a `MenuActionEvent` when the focused
+/// button gets activated. Also handles pausing while playing.
+pub fn menu_navigation(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    game_state: Res<GameStateInfo>,
+    mut menu_info: ResMut<MenuInfo>,
+    interactions: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    buttons: Query<&MenuButton>,
+    mut menu_actions: EventWriter<MenuActionEvent>,
+) {
+    let gamepad_pressed = |button_type: GamepadButtonType| {
+        gamepad_input
+            .get_just_pressed()
+            .any(|button| button.button_type == button_type)
+    };
+
+    let page = match menu_info.current_page(&game_state) {
+        Some(page) => page,
+        None => {
+            if keyboard_input.just_pressed(KeyCode::Escape)
+                || gamepad_pressed(GamepadButtonType::Start)
+            {
+                menu_actions.send(MenuActionEvent(MenuAction::Pause));
+            }
+            return;
+        }
+    };
+
+    let page_buttons: Vec<&MenuButton> = buttons
+        .iter()
+        .filter(|button| button.page == page)
+        .collect();
+    let button_count = page_buttons.len().max(1);
+
+    if keyboard_input.any_just_pressed([KeyCode::Up, KeyCode::W])
+        || gamepad_pressed(GamepadButtonType::DPadUp)
+    {
+        menu_info.focused = (menu_info.focused + button_count - 1) % button_count;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Down, KeyCode::S])
+        || gamepad_pressed(GamepadButtonType::DPadDown)
+    {
+        menu_info.focused = (menu_info.focused + 1) % button_count;
+    }
+
+    for (interaction, button) in interactions.iter() {
+        if button.page != page {
+            continue;
+        }
+        match interaction {
+            Interaction::Clicked => {
+                menu_info.focused = button.index;
+                menu_actions.send(MenuActionEvent(button.action));
+            }
+            Interaction::Hovered => {
+                menu_info.focused = button.index;
+            }
+            Interaction::None => {}
+        }
+    }
+
+    if keyboard_input.any_just_pressed([KeyCode::Return, KeyCode::Space])
+        || gamepad_pressed(GamepadButtonType::South)
+    {
+        if let Some(button) = page_buttons
+            .iter()
+            .find(|button| button.index == menu_info.focused)
+        {
+            menu_actions.send(MenuActionEvent(button.action));
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape)
+        || gamepad_pressed(GamepadButtonType::East)
+        || gamepad_pressed(GamepadButtonType::Start)
+    {
+        match page {
+            MenuPage::Settings | MenuPage::HighScores | MenuPage::Shop => {
+                menu_actions.send(MenuActionEvent(MenuAction::Back));
+            }
+            MenuPage::Pause => {
+                menu_actions.send(MenuActionEvent(MenuAction::Resume));
+            }
+            // an upgrade has to be picked to carry on
+            MenuPage::Main | MenuPage::Lose | MenuPage::Win | MenuPage::Upgrade => {}
+        }
+    }
+}
+
+/// Shows the page for the current state, resets the focus when the page changes and highlights the
+/// focused button.
+pub fn update_menus(
+    game_state: Res<GameStateInfo>,
+    settings: Res<GameSettings>,
+    high_scores: Res<HighScores>,
+    daily_results: Res<DailyResults>,
+    upgrade_offer: Res<UpgradeOffer>,
+    profile: Res<Profile>,
+    mut menu_info: ResMut<MenuInfo>,
+    mut widgets: Query<(&MenuWidget, &mut Visibility)>,
+    mut buttons: Query<(&MenuButton, &mut UiColor)>,
+    mut button_texts: Query<
+        (&MenuButtonText, &mut Text),
+        (Without<HighScoresText>, Without<ShopCreditsText>),
+    >,
+    mut high_scores_text: Query<&mut Text, (With<HighScoresText>, Without<ShopCreditsText>)>,
+    mut shop_credits_text: Query<&mut Text, (With<ShopCreditsText>, Without<MenuButtonText>)>,
+) {
+    let page = menu_info.current_page(&game_state);
+    if page != menu_info.shown_page {
+        menu_info.shown_page = page;
+        menu_info.focused = 0;
+    }
+
+    for (widget, mut visibility) in widgets.iter_mut() {
+        visibility.is_visible = Some(widget.0) == page;
+    }
+
+    for (button, mut color) in buttons.iter_mut() {
+        if Some(button.page) == page && button.index == menu_info.focused {
+            color.0 = FOCUSED_BUTTON_COLOR;
+        } else {
+            color.0 = BUTTON_COLOR;
+        }
+    }
+
+    if settings.is_changed() || upgrade_offer.is_changed() || profile.is_changed() {
+        for (button_text, mut text) in button_texts.iter_mut() {
+            match button_text.0 {
+                MenuAction::CycleSetting(setting) => {
+                    text.sections[0].value = settings.label(setting)
+                }
+                MenuAction::ChooseUpgrade(choice) => {
+                    text.sections[0].value = String::from(choice_label(&upgrade_offer, choice))
+                }
+                MenuAction::ShopItem(index) => text.sections[0].value = profile.shop_label(index),
+                _ => {}
+            }
+        }
+    }
+
+    // the table shown is the one of the mode picked in the settings
+    if high_scores.is_changed() || daily_results.is_changed() || settings.is_changed() {
+        let mut high_scores_text = high_scores_text.single_mut();
+        let entries = high_scores.entries(settings.game_mode);
+        let mut value = format!("{}\n", settings.game_mode.name().to_uppercase());
+        if entries.is_empty() {
+            value.push_str("No scores yet\n");
+        } else {
+            value.extend(
+                entries
+                    .iter()
+                    .enumerate()
+                    .map(|(place, entry)| format!("{}. {}\n", place + 1, entry.score)),
+            );
+        }
+        let daily_bests = daily_results.recent_bests();
+        if !daily_bests.is_empty() {
+            value.push_str("\nDAILY\n");
+            for (date, score) in daily_bests {
+                value.push_str(&format!("{}: {}\n", date, score));
+            }
+        }
+        high_scores_text.sections[0].value = value;
+        high_scores_text.sections[0].style.color = TEXT_COLOR;
+    }
+
+    if profile.is_changed() {
+        shop_credits_text.single_mut().sections[0].value = format!("CREDITS: {}", profile.credits);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::definitions_units::{Enemy, Health, Player, PlayerCam, Powerup};
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::players::MAX_PLAYERS;
+use crate::settings::GameSettings;
+use crate::theme::ThemePalette;
+use bevy::prelude::*;
+use std::iter::repeat_n;
+
+const MINIMAP_SCREEN_FRACTION: f32 = 0.22; // size of the minimap as a fraction of the window height
+const MINIMAP_PADDING: Val = Val::Px(5.0);
+const MINIMAP_BACKGROUND_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const MINIMAP_MARKER_SIZE: f32 = 2.5; // percent of the minimap
+const MINIMAP_PICKUP_MARKER_SIZE: f32 = 4.;
+const MAX_PICKUP_MARKERS: usize = 24;
+const HEAT_SATURATION: f32 = 6.; // enemies in one cell needed for a fully shaded cell
+
+pub struct MinimapSettings {
+    pub radius: f32, // world distance from the middle of the view to the edge of the minimap
+    pub max_enemy_markers: usize, // above this many enemies in range the minimap switches to heat shading
+    pub heat_grid_resolution: usize,
+}
+
+impl FromWorld for MinimapSettings {
+    fn from_world(_world: &mut World) -> Self {
+        MinimapSettings {
+            radius: 3000.,
+            max_enemy_markers: 120,
+            heat_grid_resolution: 16,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MinimapMarkerKind {
+    Player,
+    Enemy,
+    Health,
+    Powerup,
+}
+
+impl MinimapMarkerKind {
+    fn color(&self, palette: &ThemePalette) -> Color {
+        match self {
+            MinimapMarkerKind::Player => palette.player,
+            MinimapMarkerKind::Enemy => palette.enemy,
+            MinimapMarkerKind::Health => palette.health,
+            MinimapMarkerKind::Powerup => palette.powerup,
+        }
+    }
+
+    fn size(&self) -> f32 {
+        match self {
+            MinimapMarkerKind::Player => MINIMAP_PICKUP_MARKER_SIZE,
+            MinimapMarkerKind::Enemy => MINIMAP_MARKER_SIZE,
+            MinimapMarkerKind::Health => MINIMAP_PICKUP_MARKER_SIZE,
+            MinimapMarkerKind::Powerup => MINIMAP_PICKUP_MARKER_SIZE,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Minimap;
+#[derive(Component)]
+pub struct MinimapMarker(MinimapMarkerKind); // pooled, positions get reassigned every frame
+#[derive(Component)]
+pub struct MinimapHeatCell {
+    x: usize,
+    y: usize,
+}
+
+pub fn setup_minimap(mut commands: Commands, settings: Res<MinimapSettings>) {
+    let cell_size = 100. / settings.heat_grid_resolution as f32;
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: MINIMAP_PADDING,
+                    right: MINIMAP_PADDING,
+                    ..default()
+                },
+                size: Size::new(Val::Px(200.), Val::Px(200.)),
+                ..default()
+            },
+            color: UiColor(MINIMAP_BACKGROUND_COLOR),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(Minimap)
+        .with_children(|parent| {
+            // heat cells go first so markers draw on top of them
+            for x in 0..settings.heat_grid_resolution {
+                for y in 0..settings.heat_grid_resolution {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                position: UiRect {
+                                    left: Val::Percent(x as f32 * cell_size),
+                                    bottom: Val::Percent(y as f32 * cell_size),
+                                    ..default()
+                                },
+                                size: Size::new(Val::Percent(cell_size), Val::Percent(cell_size)),
+                                ..default()
+                            },
+                            color: UiColor(Color::NONE),
+                            visibility: Visibility { is_visible: false },
+                            ..default()
+                        })
+                        .insert(MinimapHeatCell { x, y });
+                }
+            }
+
+            let pool = repeat_n(MinimapMarkerKind::Enemy, settings.max_enemy_markers)
+                .chain(repeat_n(MinimapMarkerKind::Health, MAX_PICKUP_MARKERS))
+                .chain(repeat_n(MinimapMarkerKind::Powerup, MAX_PICKUP_MARKERS))
+                .chain(repeat_n(MinimapMarkerKind::Player, MAX_PLAYERS));
+            for kind in pool {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            size: Size::new(Val::Percent(kind.size()), Val::Percent(kind.size())),
+                            ..default()
+                        },
+                        color: UiColor(Color::NONE), // set from the palette while drawing
+                        visibility: Visibility { is_visible: false },
+                        ..default()
+                    })
+                    .insert(MinimapMarker(kind));
+            }
+        });
+}
+
+/// Draws everything within `MinimapSettings::radius` of the middle of the view. Enemies are drawn one marker each
+/// until there are more than `max_enemy_markers` of them, then they are shown as a density heat map.
+/// There are no obstacles in the game yet so only units and pickups get drawn.
+pub fn update_minimap(
+    windows: Res<Windows>,
+    game_state: Res<GameStateInfo>,
+    settings: Res<MinimapSettings>,
+    game_settings: Res<GameSettings>,
+    camera: Query<&Transform, With<PlayerCam>>,
+    players: Query<&Transform, With<Player>>,
+    enemies: Query<&Transform, With<Enemy>>,
+    health_pickups: Query<&Transform, With<Health>>,
+    powerup_pickups: Query<&Transform, With<Powerup>>,
+    mut minimap: Query<
+        (&mut Style, &mut Visibility),
+        (
+            With<Minimap>,
+            Without<MinimapMarker>,
+            Without<MinimapHeatCell>,
+        ),
+    >,
+    mut markers: Query<
+        (&MinimapMarker, &mut Style, &mut UiColor, &mut Visibility),
+        (Without<Minimap>, Without<MinimapHeatCell>),
+    >,
+    mut heat_cells: Query<
+        (&MinimapHeatCell, &mut UiColor, &mut Visibility),
+        (Without<Minimap>, Without<MinimapMarker>),
+    >,
+) {
+    let (mut minimap_style, mut minimap_visibility) = minimap.single_mut();
+    let playing = game_state.game_state == GamePlayState::Playing;
+    minimap_visibility.is_visible = playing;
+
+    if let Some(window) = windows.get_primary() {
+        let side = window.height() * MINIMAP_SCREEN_FRACTION;
+        minimap_style.size = Size::new(Val::Px(side), Val::Px(side));
+    }
+
+    // the camera follows the middle of all the players, so that is the middle of the minimap too
+    let center = camera.single().translation.truncate();
+    let to_minimap = |transform: &Transform| -> Option<Vec2> {
+        let relative = (transform.translation.truncate() - center) / settings.radius;
+        if relative.x.abs() > 1. || relative.y.abs() > 1. {
+            return None;
+        }
+        // 0 to 1 across the minimap
+        Some((relative + Vec2::ONE) * 0.5)
+    };
+
+    let enemy_points: Vec<Vec2> = enemies.iter().filter_map(to_minimap).collect();
+    let use_heat_map = enemy_points.len() > settings.max_enemy_markers;
+
+    let mut enemy_points_iter = enemy_points.iter().filter(|_| !use_heat_map);
+    let mut health_points = health_pickups.iter().filter_map(to_minimap);
+    let mut powerup_points = powerup_pickups.iter().filter_map(to_minimap);
+    let mut player_points = players.iter().filter_map(to_minimap);
+
+    let palette = game_settings.palette();
+    for (marker, mut style, mut color, mut visibility) in markers.iter_mut() {
+        let point = match marker.0 {
+            MinimapMarkerKind::Player => player_points.next(),
+            MinimapMarkerKind::Enemy => enemy_points_iter.next().copied(),
+            MinimapMarkerKind::Health => health_points.next(),
+            MinimapMarkerKind::Powerup => powerup_points.next(),
+        };
+
+        match point {
+            Some(point) if playing => {
+                let half_size = marker.0.size() * 0.5;
+                visibility.is_visible = true;
+                color.0 = marker.0.color(&palette);
+                style.position = UiRect {
+                    left: Val::Percent(point.x * 100. - half_size),
+                    bottom: Val::Percent(point.y * 100. - half_size),
+                    ..default()
+                };
+            }
+            _ => {
+                visibility.is_visible = false;
+            }
+        }
+    }
+
+    let resolution = settings.heat_grid_resolution;
+    let mut heat = vec![0u32; resolution * resolution];
+    if use_heat_map {
+        for point in enemy_points.iter() {
+            let x = ((point.x * resolution as f32) as usize).min(resolution - 1);
+            let y = ((point.y * resolution as f32) as usize).min(resolution - 1);
+            heat[x * resolution + y] += 1;
+        }
+    }
+
+    for (cell, mut color, mut visibility) in heat_cells.iter_mut() {
+        let count = heat[cell.x * resolution + cell.y];
+        visibility.is_visible = playing && count > 0;
+        let shade = (count as f32 / HEAT_SATURATION).min(1.);
+        color.0 = Color::rgba(
+            palette.enemy.r(),
+            palette.enemy.g(),
+            palette.enemy.b(),
+            0.15 + shade * 0.75,
+        );
+    }
+}
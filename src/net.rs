@@ -0,0 +1,854 @@
+use crate::config::{GameConfig, InputBindings};
+use crate::damage::Hitpoints;
+use crate::definitions_units::Player;
+use crate::enemy_spawner::SpawnRng;
+use crate::flight::FlightModel;
+use crate::game_mode::GameMode;
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::players::{player_gamepad, read_controls, FrameControls, PlayerControls};
+use crate::settings::GameSettings;
+use crate::weapon::WeaponMode;
+use crate::{GameTickInfo, Score, TIME_STEP};
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+pub const NET_PLAYERS: usize = 2; // the host is player 0, whoever joins is player 1
+const INPUT_DELAY: u32 = 3; // steps between reading a local input and simulating it, hides the round trip
+const MAX_STEP_BACKLOG: f32 = TIME_STEP * 4.; // lets a peer that fell behind catch up a little
+const MAX_INPUTS_PER_PACKET: usize = 255;
+const HELLO_INTERVAL: f32 = 0.25;
+const MAX_PACKET_SIZE: usize = 1024; // setup packets carry the whole config as text
+const SYNC_INTERVAL: u32 = 30; // steps between comparing the state of the simulation with the peer
+const KEPT_SYNC_HASHES: usize = 8; // enough to cover how far apart the peers' steps can be
+
+const PACKET_HELLO: u8 = 0; // joining peer asking the host to let it in
+const PACKET_WELCOME: u8 = 1; // host answering with the seed the session plays with
+const PACKET_INPUTS: u8 = 2;
+const PACKET_SYNC: u8 = 3; // hash of the simulation after a step, to notice the peers drifting apart
+const PACKET_SETUP: u8 = 4; // host's settings for a new run, numbers the run for the joining peer
+
+/// Optional network session between two copies of the game, started from the command line with
+/// `--host <port>` on one machine and `--join <address:port>` on the other. They can be run as two
+/// processes on 127.0.0.1 to try it out on one machine.
+///
+/// Peers run in lockstep: every `TIME_STEP` each of them sends the controls of its one local player,
+/// and a step is only simulated once both players' controls for it have arrived. Local controls are
+/// delayed by `INPUT_DELAY` steps so they usually arrive before they are needed, and every packet
+/// repeats the inputs the other peer hasn't confirmed yet so a lost packet is simply covered by the
+/// next one. While waiting, rapier and everything reading `FrameControls` stand still.
+///
+/// Staying in sync relies on both peers running the same build. Every run is played with the host's
+/// `RunSetup`, which the joining peer takes before its first step. Spawning uses `SpawnRng` seeded
+/// from the host's seed at the start of every run, the gameplay systems run in a fixed order within
+/// each step and physics runs with a fixed timestep and rapier's enhanced determinism. Every
+/// `SYNC_INTERVAL` steps the peers swap a hash of the simulation, a mismatch is logged as a desync.
+pub struct NetState {
+    session: Option<NetSession>,
+}
+
+struct NetSession {
+    socket: UdpSocket,
+    peer: Option<SocketAddr>, // the joining peer isn't known to the host until it says hello
+    is_host: bool,
+    seed: Option<u64>, // chosen by the host, the joining peer can't start until it has it
+    run: u32,          // counts runs so inputs from the previous one are ignored, set by the host
+    waiting_for_run: bool, // the joining peer started a run and waits for the host's setup of it
+    step: u32,         // next step to simulate in this run
+    local_inputs: Vec<u8>, // by step, filled `INPUT_DELAY` steps ahead
+    remote_inputs: Vec<u8>, // by step, only ever extended with the next step in order
+    peer_received: u32, // how many of `local_inputs` the peer has confirmed
+    pending_dash: bool, // dash presses between steps are kept for the next recorded input
+    step_time: f32,    // real time waiting to be simulated
+    hello_timer: f32,
+    local_hashes: Vec<(u32, u64)>, // (step, hash) of the last few synced steps of this run
+    remote_hashes: Vec<(u32, u64)>,
+    desynced: bool, // only reported once per run
+    // the host's setup of the current run, on the joining peer one that arrived and isn't applied yet
+    setup: Option<RunSetup>,
+}
+
+impl FromWorld for NetState {
+    fn from_world(_world: &mut World) -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let option = |name: &str| {
+            args.iter()
+                .position(|arg| arg == name)
+                .and_then(|index| args.get(index + 1))
+        };
+
+        let session = if let Some(port) = option("--host") {
+            NetSession::host(port)
+        } else if let Some(address) = option("--join") {
+            NetSession::join(address)
+        } else {
+            return NetState { session: None };
+        };
+
+        match session {
+            Ok(session) => NetState {
+                session: Some(session),
+            },
+            Err(error) => {
+                error!(
+                    "Couldn't start the network session, playing offline: {}",
+                    error
+                );
+                NetState { session: None }
+            }
+        }
+    }
+}
+
+impl NetState {
+    pub fn is_online(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// Whether this is the joining peer of a session, which plays with the host's settings.
+    pub fn is_joined(&self) -> bool {
+        self.session
+            .as_ref()
+            .is_some_and(|session| !session.is_host)
+    }
+
+    /// Starts counting steps for a new run. Both peers need to call this when they start or restart
+    /// a run, the run only moves once they both have. Only the host numbers runs, the joining peer
+    /// takes the number from the setup of a newer run the host sends, so the two can't count
+    /// differently however often either of them restarts. The joining peer's own setup is replaced by
+    /// the host's.
+    pub fn start_run(&mut self, setup: RunSetup) {
+        if let Some(session) = self.session.as_mut() {
+            if session.is_host {
+                session.run += 1;
+                session.setup = Some(setup);
+            } else {
+                session.waiting_for_run = true;
+            }
+            session.step = 0;
+            // the first steps happen before any input could have arrived, both peers treat them as
+            // no input
+            session.local_inputs = vec![0; INPUT_DELAY as usize];
+            session.remote_inputs = vec![0; INPUT_DELAY as usize];
+            session.peer_received = INPUT_DELAY;
+            session.pending_dash = false;
+            session.step_time = 0.;
+            session.local_hashes.clear();
+            session.remote_hashes.clear();
+            session.desynced = false;
+        }
+    }
+}
+
+impl NetSession {
+    fn host(port: &str) -> std::io::Result<NetSession> {
+        let port: u16 = port
+            .parse()
+            .map_err(|_| std::io::Error::new(ErrorKind::InvalidInput, "invalid port"))?;
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        info!("Hosting on port {}", port);
+        NetSession::new(socket, None, true, Some(thread_rng().gen()))
+    }
+
+    fn join(address: &str) -> std::io::Result<NetSession> {
+        let peer = address.to_socket_addrs()?.next().ok_or_else(|| {
+            std::io::Error::new(ErrorKind::InvalidInput, "address didn't resolve")
+        })?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        info!("Joining {}", peer);
+        NetSession::new(socket, Some(peer), false, None)
+    }
+
+    fn new(
+        socket: UdpSocket,
+        peer: Option<SocketAddr>,
+        is_host: bool,
+        seed: Option<u64>,
+    ) -> std::io::Result<NetSession> {
+        socket.set_nonblocking(true)?;
+        Ok(NetSession {
+            socket,
+            peer,
+            is_host,
+            seed,
+            run: 0,
+            waiting_for_run: false,
+            step: 0,
+            local_inputs: Vec::new(),
+            remote_inputs: Vec::new(),
+            peer_received: 0,
+            pending_dash: false,
+            step_time: 0.,
+            hello_timer: 0.,
+            local_hashes: Vec::new(),
+            remote_hashes: Vec::new(),
+            desynced: false,
+            setup: None,
+        })
+    }
+
+    fn send(&self, packet: &[u8]) {
+        if let Some(peer) = self.peer {
+            // a lost packet is covered by the next one, nothing to do about errors here
+            let _ = self.socket.send_to(packet, peer);
+        }
+    }
+
+    fn receive(&mut self) {
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        loop {
+            let (size, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => return,
+                Err(error) => {
+                    warn!("Network error: {}", error);
+                    return;
+                }
+            };
+            let packet = &buffer[..size];
+
+            if self.is_host && self.peer.is_none() && packet.first() == Some(&PACKET_HELLO) {
+                info!("{} joined", from);
+                self.peer = Some(from);
+            }
+            if self.peer != Some(from) {
+                continue;
+            }
+
+            match packet.first() {
+                Some(&PACKET_HELLO) if self.is_host => {
+                    let mut welcome = vec![PACKET_WELCOME];
+                    welcome.extend_from_slice(&self.seed.unwrap_or(0).to_le_bytes());
+                    self.send(&welcome);
+                }
+                Some(&PACKET_WELCOME) if !self.is_host && self.seed.is_none() => {
+                    if let Some(seed) = read_u64(packet, 1) {
+                        info!("Joined the session");
+                        self.seed = Some(seed);
+                    }
+                }
+                Some(&PACKET_SETUP) if !self.is_host => self.receive_setup(packet),
+                Some(&PACKET_INPUTS) => self.receive_inputs(packet),
+                Some(&PACKET_SYNC) => self.receive_sync(packet),
+                _ => {}
+            }
+        }
+    }
+
+    /// Setup packets are `run, setup`.
+    fn receive_setup(&mut self, packet: &[u8]) {
+        let run = match read_u32(packet, 1) {
+            Some(run) => run,
+            None => return,
+        };
+        if !self.waiting_for_run || run <= self.run {
+            return;
+        }
+        if let Some(setup) = packet.get(5..).and_then(RunSetup::decode) {
+            self.run = run;
+            self.waiting_for_run = false;
+            self.setup = Some(setup);
+        }
+    }
+
+    /// Inputs packets are `run, inputs received from us, first step, count, inputs...`.
+    fn receive_inputs(&mut self, packet: &[u8]) {
+        let (run, received, first_step, count) = match (
+            read_u32(packet, 1),
+            read_u32(packet, 5),
+            read_u32(packet, 9),
+            packet.get(13),
+        ) {
+            (Some(run), Some(received), Some(first_step), Some(count)) => {
+                (run, received, first_step, *count as usize)
+            }
+            _ => return,
+        };
+        if run != self.run || self.waiting_for_run {
+            return;
+        }
+
+        self.peer_received = self.peer_received.max(received);
+        for (offset, input) in packet.iter().skip(14).take(count).enumerate() {
+            if first_step as usize + offset == self.remote_inputs.len() {
+                self.remote_inputs.push(*input);
+            }
+        }
+    }
+
+    /// Sends the inputs the peer hasn't confirmed yet. Until the joining peer's first inputs of the
+    /// run show up the host sends the run's setup ahead of them.
+    fn send_inputs(&self) {
+        if self.waiting_for_run {
+            return;
+        }
+        if let Some(setup) = self.setup.as_ref() {
+            if self.is_host && self.remote_inputs.len() <= INPUT_DELAY as usize {
+                let mut packet = vec![PACKET_SETUP];
+                packet.extend_from_slice(&self.run.to_le_bytes());
+                packet.extend_from_slice(&setup.encode());
+                self.send(&packet);
+            }
+        }
+        let first = self.peer_received as usize;
+        let unconfirmed = self.local_inputs.get(first..).unwrap_or(&[]);
+        let count = unconfirmed.len().min(MAX_INPUTS_PER_PACKET);
+
+        let mut packet = vec![PACKET_INPUTS];
+        packet.extend_from_slice(&self.run.to_le_bytes());
+        packet.extend_from_slice(&(self.remote_inputs.len() as u32).to_le_bytes());
+        packet.extend_from_slice(&(first as u32).to_le_bytes());
+        packet.push(count as u8);
+        packet.extend_from_slice(&unconfirmed[..count]);
+        self.send(&packet);
+    }
+
+    /// Records local controls for every step up to `INPUT_DELAY` ahead of the one being simulated.
+    fn record_local_input(&mut self, controls: PlayerControls) {
+        while self.local_inputs.len() as u32 <= self.step + INPUT_DELAY {
+            let controls = PlayerControls {
+                dash: controls.dash || self.pending_dash,
+                ..controls
+            };
+            self.pending_dash = false;
+            self.local_inputs.push(encode_controls(controls));
+        }
+    }
+
+    /// Sync packets are `run, step, hash`.
+    fn receive_sync(&mut self, packet: &[u8]) {
+        if let (Some(run), Some(step), Some(hash)) = (
+            read_u32(packet, 1),
+            read_u32(packet, 5),
+            read_u64(packet, 9),
+        ) {
+            if run == self.run && !self.waiting_for_run {
+                keep_hash(&mut self.remote_hashes, step, hash);
+                self.compare_hashes();
+            }
+        }
+    }
+
+    /// Remembers the hash of the simulation after `step` and sends it to the peer.
+    fn sync(&mut self, step: u32, hash: u64) {
+        keep_hash(&mut self.local_hashes, step, hash);
+        let mut packet = vec![PACKET_SYNC];
+        packet.extend_from_slice(&self.run.to_le_bytes());
+        packet.extend_from_slice(&step.to_le_bytes());
+        packet.extend_from_slice(&hash.to_le_bytes());
+        self.send(&packet);
+        self.compare_hashes();
+    }
+
+    fn compare_hashes(&mut self) {
+        let mismatch = self.local_hashes.iter().find_map(|(step, hash)| {
+            self.remote_hashes
+                .iter()
+                .find(|(remote_step, remote_hash)| remote_step == step && remote_hash != hash)
+                .map(|_| *step)
+        });
+        if let Some(step) = mismatch {
+            if !self.desynced {
+                error!("Lost sync with the peer at step {} of this run", step);
+            }
+            self.desynced = true;
+        }
+    }
+
+    /// Records the local controls and moves on to the next step once both players' controls for the
+    /// current one are known, returning them by player id.
+    fn advance(&mut self, controls: PlayerControls) -> Option<[u8; NET_PLAYERS]> {
+        if self.waiting_for_run {
+            return None;
+        }
+        self.record_local_input(controls);
+        let inputs = self.step_inputs()?;
+        self.step += 1;
+        Some(inputs)
+    }
+
+    /// Both players' controls for the current step, by player id, once they are both known.
+    fn step_inputs(&self) -> Option<[u8; NET_PLAYERS]> {
+        let step = self.step as usize;
+        let local = *self.local_inputs.get(step)?;
+        let remote = *self.remote_inputs.get(step)?;
+        if self.is_host {
+            Some([local, remote])
+        } else {
+            Some([remote, local])
+        }
+    }
+}
+
+/// The settings a run of a network session is played with, the host's for both peers.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RunSetup {
+    pub config: GameConfig,
+    pub game_mode: GameMode,
+    pub flight_model: FlightModel,
+    pub weapon_mode: WeaponMode,
+}
+
+impl RunSetup {
+    pub fn new(config: &GameConfig, settings: &GameSettings) -> RunSetup {
+        RunSetup {
+            config: config.clone(),
+            game_mode: settings.game_mode,
+            flight_model: settings.flight_model,
+            weapon_mode: settings.weapon_mode,
+        }
+    }
+
+    fn apply(&self, config: &mut GameConfig, settings: &mut GameSettings) {
+        *config = self.config.clone();
+        settings.game_mode = self.game_mode;
+        settings.flight_model = self.flight_model;
+        settings.weapon_mode = self.weapon_mode;
+    }
+
+    /// The game mode, flight model and weapon mode by their index in `ALL`, then the config as text.
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![
+            index_of(&GameMode::ALL, &self.game_mode),
+            index_of(&FlightModel::ALL, &self.flight_model),
+            index_of(&WeaponMode::ALL, &self.weapon_mode),
+        ];
+        bytes.extend_from_slice(self.config.to_config_string().as_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<RunSetup> {
+        Some(RunSetup {
+            game_mode: *GameMode::ALL.get(*bytes.first()? as usize)?,
+            flight_model: *FlightModel::ALL.get(*bytes.get(1)? as usize)?,
+            weapon_mode: *WeaponMode::ALL.get(*bytes.get(2)? as usize)?,
+            config: GameConfig::parse(std::str::from_utf8(bytes.get(3..)?).ok()?),
+        })
+    }
+}
+
+fn index_of<T: PartialEq>(all: &[T], value: &T) -> u8 {
+    all.iter().position(|item| item == value).unwrap_or(0) as u8
+}
+
+fn keep_hash(hashes: &mut Vec<(u32, u64)>, step: u32, hash: u64) {
+    hashes.push((step, hash));
+    if hashes.len() > KEPT_SYNC_HASHES {
+        hashes.remove(0);
+    }
+}
+
+fn read_u32(packet: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(packet.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(packet: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(packet.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn encode_controls(controls: PlayerControls) -> u8 {
+    controls.forward as u8
+        | (controls.turn_left as u8) << 1
+        | (controls.turn_right as u8) << 2
+        | (controls.dash as u8) << 3
+        | (controls.fire as u8) << 4
+}
+
+fn decode_controls(input: u8) -> PlayerControls {
+    PlayerControls {
+        forward: input & 1 != 0,
+        turn_left: input & 1 << 1 != 0,
+        turn_right: input & 1 << 2 != 0,
+        dash: input & 1 << 3 != 0,
+        fire: input & 1 << 4 != 0,
+    }
+}
+
+/// Network sessions are always two players and need physics to step by a fixed amount.
+pub fn setup_net(
+    net: Res<NetState>,
+    mut settings: ResMut<GameSettings>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    if !net.is_online() {
+        return;
+    }
+    settings.player_count = NET_PLAYERS;
+    rapier_config.timestep_mode = TimestepMode::Fixed {
+        dt: TIME_STEP,
+        substeps: 1,
+    };
+}
+
+/// Exchanges inputs with the peer and decides whether this frame simulates the next step, filling
+/// `FrameControls` with both players' controls when it does.
+pub fn run_lockstep(
+    time: Res<Time>,
+    game_state: Res<GameStateInfo>,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    bindings: Res<InputBindings>,
+    mut net: ResMut<NetState>,
+    mut frame_controls: ResMut<FrameControls>,
+    mut spawn_rng: ResMut<SpawnRng>,
+    mut config: ResMut<GameConfig>,
+    mut settings: ResMut<GameSettings>,
+) {
+    let session = match net.session.as_mut() {
+        Some(session) => session,
+        None => return,
+    };
+    session.receive();
+    // the joining peer takes the host's setup before the first step of the run
+    if !session.is_host {
+        if let Some(setup) = session.setup.take() {
+            setup.apply(&mut config, &mut settings);
+        }
+    }
+
+    if session.seed.is_none() {
+        session.hello_timer -= time.delta_seconds();
+        if session.hello_timer <= 0. {
+            session.hello_timer = HELLO_INTERVAL;
+            session.send(&[PACKET_HELLO]);
+        }
+    }
+
+    // whoever is at this keyboard plays with the first player's bindings and gamepad
+    let local_controls = read_controls(
+        &bindings.players[0],
+        player_gamepad(0, &gamepads),
+        &keyboard_input,
+        &gamepad_buttons,
+        &gamepad_axes,
+    );
+    // menu confirm shares the dash buttons, a press that starts or resumes the run isn't a dash
+    if game_state.game_state == GamePlayState::Playing {
+        session.pending_dash |= local_controls.dash;
+    }
+
+    frame_controls.simulate = false;
+    frame_controls.delta = 0.;
+    frame_controls.players = Default::default();
+
+    if game_state.game_state == GamePlayState::Playing && session.seed.is_some() {
+        session.step_time = (session.step_time + time.delta_seconds()).min(MAX_STEP_BACKLOG);
+        if session.step_time >= TIME_STEP {
+            let first_step = session.step == 0;
+            if let Some(inputs) = session.advance(local_controls) {
+                if first_step {
+                    let seed = session.seed.unwrap_or(0);
+                    spawn_rng.reseed(seed.wrapping_add(session.run as u64));
+                }
+                session.step_time -= TIME_STEP;
+
+                frame_controls.simulate = true;
+                frame_controls.delta = TIME_STEP;
+                for (id, input) in inputs.iter().enumerate() {
+                    frame_controls.players[id] = decode_controls(*input);
+                }
+            }
+        }
+        session.send_inputs();
+    }
+}
+
+#[derive(Default)]
+pub struct SimulationStepState {
+    accumulator: f32,
+    looping: bool,
+}
+
+/// Run criteria for systems that step by `TIME_STEP`. Offline it runs as many steps as real time
+/// has passed like `FixedTimestep`, in a network session it runs once for every lockstep step so
+/// every peer simulates the same steps.
+pub fn simulation_step(
+    time: Res<Time>,
+    net: Res<NetState>,
+    frame_controls: Res<FrameControls>,
+    mut state: Local<SimulationStepState>,
+) -> ShouldRun {
+    if net.is_online() {
+        return if frame_controls.simulate {
+            ShouldRun::Yes
+        } else {
+            ShouldRun::No
+        };
+    }
+
+    // the time only counts once per frame, not again every time the steps are checked
+    if !state.looping {
+        state.accumulator += time.delta_seconds();
+    }
+    if state.accumulator >= TIME_STEP {
+        state.accumulator -= TIME_STEP;
+        state.looping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        state.looping = false;
+        ShouldRun::No
+    }
+}
+
+/// Hashes the state of the simulation every `SYNC_INTERVAL` steps of a network session and swaps it
+/// with the peer. Runs last in the step so both peers hash the same moment. Enemies are added up in
+/// any order since the peers' entities aren't guaranteed to iterate the same way.
+pub fn sync_simulation(
+    mut net: ResMut<NetState>,
+    game_tick_time: Res<GameTickInfo>,
+    score: Res<Score>,
+    players: Query<(&Player, &Transform, &Velocity, &Hitpoints)>,
+    enemies: Query<(&Transform, &Hitpoints), Without<Player>>,
+) {
+    let session = match net.session.as_mut() {
+        Some(session) => session,
+        None => return,
+    };
+    if session.step == 0 || !session.step.is_multiple_of(SYNC_INTERVAL) {
+        return;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    game_tick_time.ticks.hash(&mut hasher);
+    score.score.to_bits().hash(&mut hasher);
+
+    let mut players: Vec<_> = players.iter().collect();
+    players.sort_by_key(|(player, _, _, _)| player.id);
+    for (player, transform, velocity, hitpoints) in players {
+        player.id.hash(&mut hasher);
+        hash_vec2(transform.translation.truncate(), &mut hasher);
+        hash_vec2(velocity.linvel, &mut hasher);
+        hitpoints.current.hash(&mut hasher);
+    }
+
+    let enemies = enemies.iter().fold(0u64, |sum, (transform, hitpoints)| {
+        let mut hasher = DefaultHasher::new();
+        hash_vec2(transform.translation.truncate(), &mut hasher);
+        hitpoints.current.hash(&mut hasher);
+        sum.wrapping_add(hasher.finish())
+    });
+    enemies.hash(&mut hasher);
+
+    let step = session.step;
+    session.sync(step, hasher.finish());
+}
+
+fn hash_vec2(value: Vec2, hasher: &mut DefaultHasher) {
+    value.x.to_bits().hash(hasher);
+    value.y.to_bits().hash(hasher);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    const SEED: u64 = 7;
+
+    fn session(net: &mut NetState) -> &mut NetSession {
+        net.session.as_mut().unwrap()
+    }
+
+    /// A host and a joined peer talking over loopback, both past the hello and welcome.
+    fn connected_pair() -> (NetState, NetState) {
+        let host_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let host_address = host_socket.local_addr().unwrap();
+        let host = NetSession::new(host_socket, None, true, Some(SEED)).unwrap();
+        let joiner_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let joiner = NetSession::new(joiner_socket, Some(host_address), false, None).unwrap();
+        let (mut host, mut joiner) = (
+            NetState {
+                session: Some(host),
+            },
+            NetState {
+                session: Some(joiner),
+            },
+        );
+
+        for _ in 0..100 {
+            session(&mut joiner).send(&[PACKET_HELLO]);
+            deliver(&mut host, &mut joiner);
+            if session(&mut joiner).seed.is_some() {
+                return (host, joiner);
+            }
+        }
+        panic!("the joining peer never got the seed");
+    }
+
+    fn deliver(host: &mut NetState, joiner: &mut NetState) {
+        sleep(Duration::from_millis(2));
+        session(host).receive();
+        session(joiner).receive();
+    }
+
+    /// Reads and throws away everything waiting on the session's socket, as if it had been lost.
+    fn drop_packets(net: &mut NetState) {
+        sleep(Duration::from_millis(2));
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        while session(net).socket.recv_from(&mut buffer).is_ok() {}
+    }
+
+    fn setup() -> RunSetup {
+        RunSetup {
+            config: GameConfig::defaults(),
+            game_mode: GameMode::Endless,
+            flight_model: FlightModel::Arcade,
+            weapon_mode: WeaponMode::Off,
+        }
+    }
+
+    fn controls(forward: bool, turn_left: bool) -> PlayerControls {
+        PlayerControls {
+            forward,
+            turn_left,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn controls_survive_encoding() {
+        for input in 0..1u8 << 5 {
+            assert_eq!(encode_controls(decode_controls(input)), input);
+        }
+        let decoded = decode_controls(encode_controls(controls(true, true)));
+        assert!(decoded.forward && decoded.turn_left);
+        assert!(!decoded.turn_right && !decoded.dash && !decoded.fire);
+    }
+
+    #[test]
+    fn peers_step_to_agreement() {
+        let (mut host, mut joiner) = connected_pair();
+        host.start_run(setup());
+        joiner.start_run(setup());
+
+        let host_input = encode_controls(controls(true, false));
+        let joiner_input = encode_controls(controls(false, true));
+        let (mut host_steps, mut joiner_steps) = (Vec::new(), Vec::new());
+        for _ in 0..1000 {
+            if let Some(inputs) = session(&mut host).advance(decode_controls(host_input)) {
+                host_steps.push(inputs);
+            }
+            if let Some(inputs) = session(&mut joiner).advance(decode_controls(joiner_input)) {
+                joiner_steps.push(inputs);
+            }
+            session(&mut host).send_inputs();
+            session(&mut joiner).send_inputs();
+            deliver(&mut host, &mut joiner);
+            if host_steps.len() >= 20 && joiner_steps.len() >= 20 {
+                break;
+            }
+        }
+
+        assert!(host_steps.len() >= 20 && joiner_steps.len() >= 20);
+        assert_eq!(session(&mut joiner).run, session(&mut host).run);
+        for step in 0..20 {
+            assert_eq!(host_steps[step], joiner_steps[step], "step {}", step);
+        }
+        // nothing is known about the first steps, after them each player's own controls show up
+        assert_eq!(host_steps[0], [0, 0]);
+        assert_eq!(host_steps[19], [host_input, joiner_input]);
+    }
+
+    #[test]
+    fn lost_inputs_are_resent() {
+        let (mut host, mut joiner) = connected_pair();
+        host.start_run(setup());
+        joiner.start_run(setup());
+
+        session(&mut host).record_local_input(controls(true, false));
+        session(&mut host).send_inputs();
+        drop_packets(&mut joiner);
+        assert!(session(&mut joiner).waiting_for_run);
+        assert_eq!(
+            session(&mut joiner).remote_inputs.len(),
+            INPUT_DELAY as usize
+        );
+
+        // the host hasn't heard that they arrived, so the next packet carries them again
+        session(&mut host).send_inputs();
+        deliver(&mut host, &mut joiner);
+        let joiner = session(&mut joiner);
+        assert!(!joiner.waiting_for_run);
+        assert_eq!(joiner.remote_inputs, session(&mut host).local_inputs);
+    }
+
+    #[test]
+    fn inputs_from_another_run_are_ignored() {
+        let (mut host, mut joiner) = connected_pair();
+        host.start_run(setup());
+        joiner.start_run(setup());
+        session(&mut host).send_inputs();
+        deliver(&mut host, &mut joiner);
+        assert_eq!(session(&mut joiner).run, 1);
+
+        // the host restarts while the joining peer is still on the old run
+        host.start_run(setup());
+        session(&mut host).record_local_input(controls(true, false));
+        session(&mut host).send_inputs();
+        session(&mut joiner).record_local_input(controls(false, true));
+        session(&mut joiner).send_inputs();
+        deliver(&mut host, &mut joiner);
+        assert_eq!(session(&mut joiner).run, 1);
+        assert_eq!(
+            session(&mut joiner).remote_inputs.len(),
+            INPUT_DELAY as usize
+        );
+        assert_eq!(session(&mut host).remote_inputs.len(), INPUT_DELAY as usize);
+
+        // once it restarts too it takes the host's run number
+        joiner.start_run(setup());
+        session(&mut host).send_inputs();
+        deliver(&mut host, &mut joiner);
+        assert_eq!(session(&mut joiner).run, 2);
+        assert_eq!(
+            session(&mut joiner).remote_inputs,
+            session(&mut host).local_inputs
+        );
+    }
+
+    #[test]
+    fn joining_peer_takes_the_hosts_setup() {
+        let (mut host, mut joiner) = connected_pair();
+        let mut host_setup = setup();
+        host_setup.config.dash_speed = 1234.5;
+        host_setup.config.enemy_contact_damage = 3;
+        host_setup.game_mode = GameMode::Target;
+        host_setup.flight_model = FlightModel::Momentum;
+        host_setup.weapon_mode = WeaponMode::Aimed;
+        host.start_run(host_setup.clone());
+        joiner.start_run(setup());
+
+        session(&mut host).send_inputs();
+        deliver(&mut host, &mut joiner);
+        let joiner = session(&mut joiner);
+        assert!(!joiner.waiting_for_run);
+        assert_eq!(joiner.setup, Some(host_setup));
+    }
+
+    #[test]
+    fn different_hashes_are_a_desync() {
+        let (mut host, mut joiner) = connected_pair();
+        host.start_run(setup());
+        joiner.start_run(setup());
+        session(&mut host).send_inputs();
+        deliver(&mut host, &mut joiner);
+
+        session(&mut host).sync(SYNC_INTERVAL, 1);
+        session(&mut joiner).sync(SYNC_INTERVAL, 1);
+        deliver(&mut host, &mut joiner);
+        assert!(!session(&mut host).desynced && !session(&mut joiner).desynced);
+
+        session(&mut host).sync(SYNC_INTERVAL * 2, 1);
+        session(&mut joiner).sync(SYNC_INTERVAL * 2, 2);
+        deliver(&mut host, &mut joiner);
+        assert!(session(&mut host).desynced && session(&mut joiner).desynced);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::definitions_units::{Health, PlayerCam, Powerup};
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::settings::GameSettings;
+use crate::theme::ThemePalette;
+use crate::FONT_ASSET_PATH;
+use bevy::prelude::*;
+
+const INDICATOR_Z: f32 = 90.; // above the player and everything else in the world
+const INDICATOR_EDGE_MARGIN: f32 = 48.;
+const INDICATOR_TEXT_OFFSET: f32 = 42.;
+const INDICATOR_FADE_DISTANCE: f32 = 300.; // distance outside the view over which the arrow fades in
+const INDICATOR_FONT_SIZE: f32 = 20.;
+const PIXELS_PER_METER: f32 = 32.;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum IndicatorTarget {
+    Health,
+    Powerup,
+}
+
+impl IndicatorTarget {
+    fn color(&self, palette: &ThemePalette) -> Color {
+        match self {
+            IndicatorTarget::Health => palette.health,
+            IndicatorTarget::Powerup => palette.powerup,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct OffscreenIndicator(IndicatorTarget); // the arrow, rotated to point at the target
+#[derive(Component)]
+pub struct OffscreenIndicatorText(IndicatorTarget); // distance readout, kept upright next to the arrow
+
+pub fn setup_offscreen_indicators(commands: &mut Commands, asset_server: &mut ResMut<AssetServer>) {
+    for target in [IndicatorTarget::Health, IndicatorTarget::Powerup] {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::WHITE, // recolored from the palette every frame
+                    custom_size: Some(Vec2::new(26., 8.)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0., 0., INDICATOR_Z),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(OffscreenIndicator(target))
+            .with_children(|parent| {
+                // arrow head
+                parent.spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::WHITE, // recolored from the palette every frame
+                        custom_size: Some(Vec2::new(14., 14.)),
+                        ..default()
+                    },
+                    transform: Transform {
+                        translation: Vec3::new(12., 0., 0.),
+                        rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+                        ..default()
+                    },
+                    ..default()
+                });
+            });
+
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load(FONT_ASSET_PATH),
+                        font_size: INDICATOR_FONT_SIZE,
+                        color: Color::WHITE, // recolored from the palette every frame
+                    },
+                )
+                .with_alignment(TextAlignment::CENTER),
+                transform: Transform::from_xyz(0., 0., INDICATOR_Z),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(OffscreenIndicatorText(target));
+    }
+}
+
+/// Points an arrow at the screen edge towards the nearest health and powerup pickup. The arrows fade
+/// out as the pickup gets close to the screen and disappear once it is in view.
+pub fn update_offscreen_indicators(
+    windows: Res<Windows>,
+    game_state: Res<GameStateInfo>,
+    settings: Res<GameSettings>,
+    camera: Query<(&Transform, &OrthographicProjection), With<PlayerCam>>,
+    health_pickups: Query<&Transform, (With<Health>, Without<PlayerCam>)>,
+    powerup_pickups: Query<&Transform, (With<Powerup>, Without<PlayerCam>)>,
+    mut indicators: Query<
+        (
+            &OffscreenIndicator,
+            &mut Transform,
+            &mut Sprite,
+            &mut Visibility,
+            &Children,
+        ),
+        (
+            Without<PlayerCam>,
+            Without<Health>,
+            Without<Powerup>,
+            Without<OffscreenIndicatorText>,
+        ),
+    >,
+    mut indicator_heads: Query<
+        (&mut Sprite, &mut Visibility),
+        (Without<OffscreenIndicator>, Without<OffscreenIndicatorText>),
+    >,
+    mut indicator_texts: Query<
+        (
+            &OffscreenIndicatorText,
+            &mut Transform,
+            &mut Text,
+            &mut Visibility,
+        ),
+        (
+            Without<PlayerCam>,
+            Without<Health>,
+            Without<Powerup>,
+            Without<OffscreenIndicator>,
+        ),
+    >,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let palette = settings.palette();
+    let (cam_transform, projection) = camera.single();
+    let camera_position = cam_transform.translation.truncate();
+    let half_view = Vec2::new(window.width(), window.height()) * 0.5 * projection.scale;
+
+    for (indicator, mut transform, mut sprite, mut visibility, children) in indicators.iter_mut() {
+        let nearest = match indicator.0 {
+            IndicatorTarget::Health => nearest_position(camera_position, health_pickups.iter()),
+            IndicatorTarget::Powerup => nearest_position(camera_position, powerup_pickups.iter()),
+        };
+
+        let placement = match nearest {
+            Some(target_position) if game_state.game_state == GamePlayState::Playing => {
+                edge_placement(
+                    camera_position,
+                    half_view,
+                    target_position,
+                    projection.scale,
+                )
+            }
+            _ => None,
+        };
+
+        let (edge_position, direction, distance, alpha) = match placement {
+            Some(placement) => placement,
+            None => {
+                visibility.is_visible = false;
+                for &child in children.iter() {
+                    if let Ok((_, mut head_visibility)) = indicator_heads.get_mut(child) {
+                        head_visibility.is_visible = false;
+                    }
+                }
+                for (text_indicator, _, _, mut text_visibility) in indicator_texts.iter_mut() {
+                    if text_indicator.0 == indicator.0 {
+                        text_visibility.is_visible = false;
+                    }
+                }
+                continue;
+            }
+        };
+
+        let color = indicator.0.color(&palette);
+        let faded_color = Color::rgba(color.r(), color.g(), color.b(), alpha);
+
+        visibility.is_visible = true;
+        sprite.color = faded_color;
+        transform.translation = edge_position.extend(INDICATOR_Z);
+        transform.rotation = Quat::from_rotation_z(f32::atan2(direction.y, direction.x));
+        transform.scale = Vec3::splat(projection.scale);
+        for &child in children.iter() {
+            if let Ok((mut head_sprite, mut head_visibility)) = indicator_heads.get_mut(child) {
+                head_sprite.color = faded_color;
+                head_visibility.is_visible = true;
+            }
+        }
+
+        for (text_indicator, mut text_transform, mut text, mut text_visibility) in
+            indicator_texts.iter_mut()
+        {
+            if text_indicator.0 != indicator.0 {
+                continue;
+            }
+            text_visibility.is_visible = true;
+            text_transform.translation = (edge_position
+                - direction * INDICATOR_TEXT_OFFSET * projection.scale)
+                .extend(INDICATOR_Z);
+            text_transform.scale = Vec3::splat(projection.scale);
+            text.sections[0].value = format!("{:.0}m", distance / PIXELS_PER_METER);
+            text.sections[0].style.color = faded_color;
+        }
+    }
+}
+
+fn nearest_position<'a>(
+    from: Vec2,
+    transforms: impl Iterator<Item = &'a Transform>,
+) -> Option<Vec2> {
+    transforms
+        .map(|transform| transform.translation.truncate())
+        .min_by(|a, b| {
+            a.distance_squared(from)
+                .total_cmp(&b.distance_squared(from))
+        })
+}
+
+/// Returns where on the screen edge the arrow goes, the direction to the target, the distance to it
+/// and how visible the arrow should be. None when the target is fully on screen.
+fn edge_placement(
+    camera_position: Vec2,
+    half_view: Vec2,
+    target_position: Vec2,
+    scale: f32,
+) -> Option<(Vec2, Vec2, f32, f32)> {
+    let offset = target_position - camera_position;
+    let outside_distance = (offset.x.abs() - half_view.x).max(offset.y.abs() - half_view.y);
+    if outside_distance <= 0. {
+        return None;
+    }
+
+    let inset = (half_view - Vec2::splat(INDICATOR_EDGE_MARGIN * scale)).max(Vec2::ONE);
+    let to_edge = (inset.x / offset.x.abs()).min(inset.y / offset.y.abs());
+    let alpha = (outside_distance / INDICATOR_FADE_DISTANCE).clamp(0., 1.);
+
+    Some((
+        camera_position + offset * to_edge,
+        offset.normalize_or_zero(),
+        offset.length(),
+        alpha,
+    ))
+}
@@ -0,0 +1,201 @@
+use crate::definitions_units::Player;
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::players::MAX_PLAYERS;
+use crate::settings::GameSettings;
+use crate::theme::ThemePalette;
+use crate::PlayerInput;
+use bevy::prelude::*;
+use rand::*;
+
+const PARTICLE_Z: f32 = 60.; // above pickups and enemies, below the player
+const THRUST_PARTICLES_PER_SECOND: f32 = 60.;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParticleBudget {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl ParticleBudget {
+    /// Most particles allowed alive at once, new particles are dropped past this.
+    pub fn max_particles(&self) -> usize {
+        match self {
+            ParticleBudget::Off => 0,
+            ParticleBudget::Low => 250,
+            ParticleBudget::Medium => 800,
+            ParticleBudget::High => 2000,
+        }
+    }
+
+    pub fn next(&self) -> ParticleBudget {
+        match self {
+            ParticleBudget::Off => ParticleBudget::Low,
+            ParticleBudget::Low => ParticleBudget::Medium,
+            ParticleBudget::Medium => ParticleBudget::High,
+            ParticleBudget::High => ParticleBudget::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParticleBudget::Off => "Off",
+            ParticleBudget::Low => "Low",
+            ParticleBudget::Medium => "Medium",
+            ParticleBudget::High => "High",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ParticleEffect {
+    EnemyDeath,
+    PlayerDamage,
+    Pickup(Color), // tinted with the color of whatever got picked up
+    Thrust,
+}
+
+impl ParticleEffect {
+    // count, speed range, lifetime range, size, color
+    fn settings(&self, palette: &ThemePalette) -> (usize, (f32, f32), (f32, f32), f32, Color) {
+        match self {
+            ParticleEffect::EnemyDeath => (14, (120., 320.), (0.3, 0.6), 8., palette.enemy),
+            ParticleEffect::PlayerDamage => (10, (80., 220.), (0.2, 0.45), 6., palette.player),
+            ParticleEffect::Pickup(color) => (18, (60., 200.), (0.4, 0.8), 7., *color),
+            ParticleEffect::Thrust => (1, (40., 90.), (0.25, 0.4), 5., palette.player),
+        }
+    }
+}
+
+/// Send to spawn a burst of particles. `direction` is only used by directional effects like the
+/// thrust trail, bursts go out in every direction.
+pub struct ParticleEvent {
+    pub effect: ParticleEffect,
+    pub position: Vec2,
+    pub direction: Vec2,
+}
+
+#[derive(Component)]
+pub struct Particle {
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    color: Color,
+    size: f32,
+}
+
+pub fn handle_particle_events(
+    mut commands: Commands,
+    settings: Res<GameSettings>,
+    mut particle_events: EventReader<ParticleEvent>,
+    particles: Query<(), With<Particle>>,
+) {
+    let mut live_particles = particles.iter().count();
+    let max_particles = settings.particle_budget.max_particles();
+    let palette = settings.palette();
+    let mut rng = thread_rng();
+
+    for event in particle_events.iter() {
+        let (count, speed, lifetime, size, color) = event.effect.settings(&palette);
+
+        for _ in 0..count {
+            if live_particles >= max_particles {
+                return;
+            }
+            live_particles += 1;
+
+            let direction = match event.effect {
+                ParticleEffect::Thrust => {
+                    let spread = rng.gen_range(-0.35..0.35);
+                    Quat::from_rotation_z(spread)
+                        .mul_vec3(event.direction.extend(0.))
+                        .truncate()
+                }
+                _ => {
+                    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                    Vec2::new(angle.cos(), angle.sin())
+                }
+            };
+
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::splat(size)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(event.position.extend(PARTICLE_Z)),
+                    ..default()
+                })
+                .insert(Particle {
+                    velocity: direction * rng.gen_range(speed.0..speed.1),
+                    age: 0.,
+                    lifetime: rng.gen_range(lifetime.0..lifetime.1),
+                    color,
+                    size,
+                });
+        }
+    }
+}
+
+/// Moves, shrinks and fades particles, despawning them at the end of their lifetime. Particles freeze
+/// while the game is paused.
+pub fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    game_state: Res<GameStateInfo>,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    if matches!(
+        game_state.game_state,
+        GamePlayState::Pause | GamePlayState::Upgrade
+    ) {
+        return;
+    }
+
+    let delta = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut sprite) in particles.iter_mut() {
+        particle.age += delta;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let remaining = 1. - particle.age / particle.lifetime;
+        transform.translation += (particle.velocity * delta).extend(0.);
+        particle.velocity *= 1. - (4. * delta).min(1.);
+        sprite.custom_size = Some(Vec2::splat(particle.size * (0.4 + remaining * 0.6)));
+        let color = particle.color;
+        sprite.color = Color::rgba(color.r(), color.g(), color.b(), remaining);
+    }
+}
+
+/// Leaves a trail behind each player while they hold forward.
+pub fn emit_thrust_trail(
+    time: Res<Time>,
+    game_state: Res<GameStateInfo>,
+    players: Query<(&Player, &PlayerInput, &Transform)>,
+    mut emit_timers: Local<[f32; MAX_PLAYERS]>,
+    mut particle_events: EventWriter<ParticleEvent>,
+) {
+    for (player, player_input, transform) in players.iter() {
+        let emit_timer = &mut emit_timers[player.id];
+        if game_state.game_state != GamePlayState::Playing || !player_input.is_holding_forward {
+            *emit_timer = 0.;
+            continue;
+        }
+
+        let backwards = -(transform.rotation * Vec3::Y).truncate();
+
+        *emit_timer += time.delta_seconds();
+        while *emit_timer >= 1. / THRUST_PARTICLES_PER_SECOND {
+            *emit_timer -= 1. / THRUST_PARTICLES_PER_SECOND;
+            particle_events.send(ParticleEvent {
+                effect: ParticleEffect::Thrust,
+                position: transform.translation.truncate() + backwards * 10.,
+                direction: backwards,
+            });
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use crate::damage::Hitpoints;
+use crate::definitions_units::{Health, Player, PlayerStats, Powerup};
+use crate::game_state::{GamePlayState, GameStateInfo};
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+const MAGNET_MIN_SPEED: f32 = 200.;
+const MAGNET_MAX_SPEED: f32 = 900.; // faster than the player so pickups can catch up
+
+/// Pulls pickups within `PlayerStats::magnet_radius` towards the closest living player whose magnet
+/// reaches them, faster the closer they are. Pickups outside every radius, or while not playing, are
+/// held still.
+pub fn attract_pickups(
+    game_state: Res<GameStateInfo>,
+    players: Query<(&Transform, &PlayerStats, &Hitpoints), With<Player>>,
+    mut pickups: Query<
+        (&Transform, &mut Velocity),
+        (Or<(With<Health>, With<Powerup>)>, Without<Player>),
+    >,
+) {
+    let playing = game_state.game_state == GamePlayState::Playing;
+
+    for (transform, mut velocity) in pickups.iter_mut() {
+        let position = transform.translation.truncate();
+        let pull = players
+            .iter()
+            .filter(|(_, _, hitpoints)| !hitpoints.is_dead())
+            .map(|(player_transform, player_stats, _)| {
+                let offset = player_transform.translation.truncate() - position;
+                (offset, offset.length(), player_stats.magnet_radius())
+            })
+            .filter(|(_, distance, radius)| *distance <= *radius && *distance > f32::EPSILON)
+            .min_by(|(_, a, _), (_, b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let (offset, distance, radius) = match pull {
+            Some(pull) if playing => pull,
+            _ => {
+                velocity.linvel = Vec2::ZERO;
+                continue;
+            }
+        };
+
+        let closeness = 1. - distance / radius;
+        let speed = MAGNET_MIN_SPEED + (MAGNET_MAX_SPEED - MAGNET_MIN_SPEED) * closeness;
+        velocity.linvel = offset / distance * speed;
+    }
+}
@@ -0,0 +1,183 @@
+use crate::config::{InputBindings, PlayerBindings};
+use crate::daily::DailyChallenge;
+use crate::definitions_units::{Player, PlayerBundle, PlayerCam};
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::net::NetState;
+use crate::settings::GameSettings;
+use bevy::prelude::*;
+
+pub const MAX_PLAYERS: usize = 4;
+const PLAYER_SPAWN_SPACING: f32 = 64.;
+const GAMEPAD_STICK_DEADZONE: f32 = 0.5;
+const CAMERA_FRAME_MARGIN: f32 = 200.; // space kept between the outermost player and the screen edge
+const CAMERA_MAX_ZOOM_OUT: f32 = 2.5;
+const CAMERA_ZOOM_SPEED: f32 = 3.; // how quickly the zoom eases towards framing everyone
+
+/// Players in the run being played. Daily challenges are single player so everyone's result is their
+/// own.
+pub fn player_count(settings: &GameSettings, daily: &DailyChallenge) -> usize {
+    if daily.date.is_some() {
+        1
+    } else {
+        settings.player_count
+    }
+}
+
+/// Spawns `count` players side by side around the origin, ids starting from 0.
+pub fn spawn_players(commands: &mut Commands, count: usize) {
+    let count = count.clamp(1, MAX_PLAYERS);
+    for id in 0..count {
+        let x = (id as f32 - (count - 1) as f32 * 0.5) * PLAYER_SPAWN_SPACING;
+        commands.spawn_bundle(PlayerBundle::new(id, Vec2::new(x, 0.)));
+    }
+}
+
+/// What one player is asking for this frame, their keys and gamepad combined.
+#[derive(Clone, Copy, Default)]
+pub struct PlayerControls {
+    pub forward: bool,
+    pub turn_left: bool,
+    pub turn_right: bool,
+    pub dash: bool, // pressed since the last simulation step, see `dash::player_dash`
+    pub fire: bool,
+}
+
+impl PlayerControls {
+    pub fn turning(&self) -> bool {
+        self.turn_left || self.turn_right
+    }
+}
+
+/// Controls of every player for this frame, gathered before the game systems run so they all see the
+/// same thing. In a network session they come from the lockstep instead of the local devices.
+#[derive(Default)]
+pub struct FrameControls {
+    pub players: [PlayerControls; MAX_PLAYERS], // indexed by `Player::id`
+    pub simulate: bool, // false while a network session waits on a peer, nothing should move
+    pub delta: f32,     // seconds the game advances this frame
+}
+
+/// Reads every local player's keys and gamepad, network sessions fill `FrameControls` themselves.
+pub fn gather_controls(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    bindings: Res<InputBindings>,
+    net: Res<NetState>,
+    game_state: Res<GameStateInfo>,
+    mut frame_controls: ResMut<FrameControls>,
+) {
+    if net.is_online() {
+        return;
+    }
+    frame_controls.simulate = true;
+    frame_controls.delta = time.delta_seconds();
+    for (id, controls) in frame_controls.players.iter_mut().enumerate() {
+        // not every frame steps the simulation, a dash pressed in between waits for the next step
+        let pending_dash = controls.dash;
+        *controls = read_controls(
+            &bindings.players[id],
+            player_gamepad(id, &gamepads),
+            &keyboard_input,
+            &gamepad_buttons,
+            &gamepad_axes,
+        );
+        controls.dash |= pending_dash;
+        // dash shares its default buttons with confirming in the menus, which starts and resumes runs
+        if game_state.game_state != GamePlayState::Playing {
+            controls.dash = false;
+        }
+    }
+}
+
+/// Gamepads are handed out in the order of their ids, so the first connected pad belongs to the first
+/// player and so on.
+pub fn player_gamepad(id: usize, gamepads: &Gamepads) -> Option<Gamepad> {
+    let mut connected: Vec<Gamepad> = gamepads.iter().copied().collect();
+    connected.sort_by_key(|gamepad| gamepad.id);
+    connected.get(id).copied()
+}
+
+pub fn read_controls(
+    bindings: &PlayerBindings,
+    gamepad: Option<Gamepad>,
+    keyboard: &Input<KeyCode>,
+    buttons: &Input<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+) -> PlayerControls {
+    let mut controls = PlayerControls {
+        forward: keyboard.pressed(bindings.forward),
+        turn_left: keyboard.pressed(bindings.turn_left),
+        turn_right: keyboard.pressed(bindings.turn_right),
+        dash: keyboard.just_pressed(bindings.dash),
+        fire: keyboard.pressed(bindings.fire),
+    };
+
+    if let Some(gamepad) = gamepad {
+        let pressed = |button_type| buttons.pressed(GamepadButton::new(gamepad, button_type));
+        let stick_x = axes
+            .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+            .unwrap_or(0.);
+        controls.forward |=
+            pressed(GamepadButtonType::RightTrigger2) || pressed(GamepadButtonType::DPadUp);
+        controls.turn_left |=
+            stick_x < -GAMEPAD_STICK_DEADZONE || pressed(GamepadButtonType::DPadLeft);
+        controls.turn_right |=
+            stick_x > GAMEPAD_STICK_DEADZONE || pressed(GamepadButtonType::DPadRight);
+        controls.dash |=
+            buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::South));
+        controls.fire |= pressed(GamepadButtonType::West);
+    }
+    controls
+}
+
+/// The closest of `points` to `from`, `None` when there are none. Used both for chasing players and
+/// for aiming at enemies.
+pub fn nearest(from: Vec2, points: impl Iterator<Item = Vec2>) -> Option<Vec2> {
+    points.min_by(|a, b| {
+        a.distance_squared(from)
+            .partial_cmp(&b.distance_squared(from))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    })
+}
+
+/// Keeps the shared camera in the middle of every player, zooming out as they spread apart so
+/// everyone stays on screen.
+pub fn frame_players(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    players: Query<&Transform, (With<Player>, Without<PlayerCam>)>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<PlayerCam>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if players.is_empty() {
+        return;
+    }
+
+    let mut min = Vec2::splat(f32::MAX);
+    let mut max = Vec2::splat(f32::MIN);
+    for transform in players.iter() {
+        let position = transform.translation.truncate();
+        min = min.min(position);
+        max = max.max(position);
+    }
+
+    let (mut cam_transform, mut projection) = camera.single_mut();
+    // keep the camera's own depth so sprites drawn above the players are still in front of them
+    cam_transform.translation = ((min + max) * 0.5).extend(cam_transform.translation.z);
+
+    let half_extent = (max - min) * 0.5 + Vec2::splat(CAMERA_FRAME_MARGIN);
+    let half_view = Vec2::new(window.width(), window.height()) * 0.5;
+    let target_scale = (half_extent / half_view)
+        .max_element()
+        .clamp(1., CAMERA_MAX_ZOOM_OUT);
+    let ease = (time.delta_seconds() * CAMERA_ZOOM_SPEED).min(1.);
+    if (target_scale - projection.scale).abs() > f32::EPSILON {
+        projection.scale += (target_scale - projection.scale) * ease;
+    }
+}
@@ -0,0 +1,121 @@
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::FONT_ASSET_PATH;
+use bevy::prelude::*;
+
+const POPUP_POOL_SIZE: usize = 256;
+const POPUP_LIFETIME: f32 = 0.8;
+const POPUP_RISE_SPEED: f32 = 90.;
+const POPUP_FONT_SIZE: f32 = 28.;
+const POPUP_Z: f32 = 95.; // above the player and the offscreen indicators
+
+/// Send this to show a floating text popup in the world at `position`.
+pub struct PopupEvent {
+    pub position: Vec2,
+    pub text: String,
+    pub color: Color,
+}
+
+#[derive(Component)]
+pub struct Popup {
+    active: bool,
+    age: f32,
+    start_position: Vec2,
+    color: Color,
+}
+
+/// Popups are spawned once up front and reused in a ring so a burst of them never spawns or despawns
+/// entities. When every popup is in use the oldest one gets recycled.
+pub struct PopupPool {
+    entities: Vec<Entity>,
+    next: usize,
+}
+
+pub fn setup_popups(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load(FONT_ASSET_PATH);
+    let mut entities = Vec::with_capacity(POPUP_POOL_SIZE);
+
+    for _ in 0..POPUP_POOL_SIZE {
+        let entity = commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::from_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: POPUP_FONT_SIZE,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_alignment(TextAlignment::CENTER),
+                transform: Transform::from_xyz(0., 0., POPUP_Z),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(Popup {
+                active: false,
+                age: 0.,
+                start_position: Vec2::ZERO,
+                color: Color::WHITE,
+            })
+            .id();
+        entities.push(entity);
+    }
+
+    commands.insert_resource(PopupPool { entities, next: 0 });
+}
+
+pub fn handle_popup_events(
+    mut popup_events: EventReader<PopupEvent>,
+    mut popup_pool: ResMut<PopupPool>,
+    mut popups: Query<(&mut Popup, &mut Text, &mut Transform, &mut Visibility)>,
+) {
+    for event in popup_events.iter() {
+        let entity = popup_pool.entities[popup_pool.next];
+        popup_pool.next = (popup_pool.next + 1) % popup_pool.entities.len();
+
+        if let Ok((mut popup, mut text, mut transform, mut visibility)) = popups.get_mut(entity) {
+            popup.active = true;
+            popup.age = 0.;
+            popup.start_position = event.position;
+            popup.color = event.color;
+            text.sections[0].value.clone_from(&event.text);
+            text.sections[0].style.color = event.color;
+            transform.translation = event.position.extend(POPUP_Z);
+            visibility.is_visible = true;
+        }
+    }
+}
+
+/// Floats active popups upwards while fading them out.
+pub fn animate_popups(
+    time: Res<Time>,
+    game_state: Res<GameStateInfo>,
+    mut popups: Query<(&mut Popup, &mut Text, &mut Transform, &mut Visibility)>,
+) {
+    if matches!(
+        game_state.game_state,
+        GamePlayState::Pause | GamePlayState::Upgrade
+    ) {
+        return;
+    }
+
+    for (mut popup, mut text, mut transform, mut visibility) in popups.iter_mut() {
+        if !popup.active {
+            continue;
+        }
+
+        popup.age += time.delta_seconds();
+        if popup.age >= POPUP_LIFETIME || game_state.game_state != GamePlayState::Playing {
+            popup.active = false;
+            visibility.is_visible = false;
+            continue;
+        }
+
+        let progress = popup.age / POPUP_LIFETIME;
+        transform.translation =
+            (popup.start_position + Vec2::new(0., popup.age * POPUP_RISE_SPEED)).extend(POPUP_Z);
+        // grow in quickly then fade out over the rest of the lifetime
+        transform.scale = Vec3::splat(0.6 + (progress * 6.).min(1.) * 0.4);
+        let color = popup.color;
+        text.sections[0].style.color = Color::rgba(color.r(), color.g(), color.b(), 1. - progress);
+    }
+}
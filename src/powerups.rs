@@ -0,0 +1,239 @@
+use crate::definitions_units::{Enemy, PlayerStats};
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::TIME_STEP;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::*;
+
+pub const SPEED_BOOST_MULTIPLIER: f32 = 1.5;
+pub const TIME_SLOW_ENEMY_SPEED: f32 = 0.4; // fraction of the normal enemy speed while time slow runs
+pub const MAGNET_RADIUS_MULTIPLIER: f32 = 3.;
+pub const POWERUP_WARNING_TIME: f32 = 1.; // powerups flash for this long before running out
+const WARNING_FLASHES_PER_SECOND: f32 = 4.;
+const SHOCKWAVE_RADIUS: f32 = 700.;
+const SHOCKWAVE_SPEED: f32 = 2500.; // speed given to enemies right next to the player, less further out
+const SHOCKWAVE_STAGGER_TIME: f32 = 0.6; // how long pushed enemies stop chasing so the push can play out
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerupKind {
+    KillMode,
+    SpeedBoost,
+    Shield,
+    Shockwave,
+    TimeSlow,
+    Magnet,
+    WeaponUpgrade,
+}
+
+impl PowerupKind {
+    pub const ALL: [PowerupKind; 7] = [
+        PowerupKind::KillMode,
+        PowerupKind::SpeedBoost,
+        PowerupKind::Shield,
+        PowerupKind::Shockwave,
+        PowerupKind::TimeSlow,
+        PowerupKind::Magnet,
+        PowerupKind::WeaponUpgrade,
+    ];
+
+    /// Relative chance of this kind when a powerup spawns, see `Spawner::powerup_weights`.
+    pub fn default_spawn_weight(&self) -> u32 {
+        match self {
+            PowerupKind::KillMode => 30,
+            PowerupKind::SpeedBoost => 20,
+            PowerupKind::Shield => 15,
+            PowerupKind::Shockwave => 10,
+            PowerupKind::TimeSlow => 10,
+            PowerupKind::Magnet => 15,
+            PowerupKind::WeaponUpgrade => 15,
+        }
+    }
+
+    /// Short label used for the hud icon.
+    pub fn icon(&self) -> &'static str {
+        match self {
+            PowerupKind::KillMode => "KILL",
+            PowerupKind::SpeedBoost => "SPD",
+            PowerupKind::Shield => "SHLD",
+            PowerupKind::Shockwave => "WAVE",
+            PowerupKind::TimeSlow => "SLOW",
+            PowerupKind::Magnet => "MAG",
+            PowerupKind::WeaponUpgrade => "GUN",
+        }
+    }
+
+    pub fn pickup_text(&self) -> &'static str {
+        match self {
+            PowerupKind::KillMode => "KILL MODE!",
+            PowerupKind::SpeedBoost => "SPEED!",
+            PowerupKind::Shield => "SHIELD!",
+            PowerupKind::Shockwave => "SHOCKWAVE!",
+            PowerupKind::TimeSlow => "TIME SLOW!",
+            PowerupKind::Magnet => "MAGNET!",
+            PowerupKind::WeaponUpgrade => "WEAPON UP!",
+        }
+    }
+
+    /// Every kind plays the powerup sound at its own pitch so they can be told apart by ear.
+    pub fn pickup_playback_rate(&self) -> f64 {
+        match self {
+            PowerupKind::KillMode => 1.0,
+            PowerupKind::SpeedBoost => 1.3,
+            PowerupKind::Shield => 0.8,
+            PowerupKind::Shockwave => 0.6,
+            PowerupKind::TimeSlow => 0.7,
+            PowerupKind::Magnet => 1.15,
+            PowerupKind::WeaponUpgrade => 1.45,
+        }
+    }
+}
+
+/// Picks a kind from `(kind, weight)` pairs, kill mode if every weight is zero.
+pub fn choose_weighted(weights: &[(PowerupKind, u32)], rng: &mut impl Rng) -> PowerupKind {
+    let total: u32 = weights.iter().map(|(_, weight)| weight).sum();
+    if total == 0 {
+        return PowerupKind::KillMode;
+    }
+    let mut roll = rng.gen_range(0..total);
+    for (kind, weight) in weights.iter() {
+        if roll < *weight {
+            return *kind;
+        }
+        roll -= weight;
+    }
+    PowerupKind::KillMode
+}
+
+/// Timed and charged powerups the player has running. Kill mode predates these and still lives
+/// directly on `PlayerStats`, shockwave is instant so it has no state.
+///
+/// Stacking rules when picking up a kind that is already running:
+/// - speed boost and magnet refresh back to their full duration
+/// - time slow adds to what is left, up to a cap
+/// - shield adds a charge, up to a cap
+#[derive(Default, Clone)]
+pub struct ActivePowerups {
+    pub speed_boost_time_left: f32,
+    pub shield_hits: i32,
+    pub time_slow_time_left: f32,
+    pub magnet_time_left: f32,
+}
+
+impl ActivePowerups {
+    const SPEED_BOOST_TIME: f32 = 5.;
+    const MAX_SHIELD_HITS: i32 = 3;
+    const TIME_SLOW_TIME: f32 = 4.;
+    const MAX_TIME_SLOW_TIME: f32 = 8.;
+    const MAGNET_TIME: f32 = 8.;
+
+    /// Starts or stacks one of the kinds tracked here, kill mode, shockwave and weapon upgrades are
+    /// ignored.
+    pub fn activate(&mut self, kind: PowerupKind) {
+        match kind {
+            PowerupKind::SpeedBoost => self.speed_boost_time_left = Self::SPEED_BOOST_TIME,
+            PowerupKind::Shield => {
+                self.shield_hits = (self.shield_hits + 1).min(Self::MAX_SHIELD_HITS)
+            }
+            PowerupKind::TimeSlow => {
+                self.time_slow_time_left =
+                    (self.time_slow_time_left + Self::TIME_SLOW_TIME).min(Self::MAX_TIME_SLOW_TIME)
+            }
+            PowerupKind::Magnet => self.magnet_time_left = Self::MAGNET_TIME,
+            PowerupKind::KillMode | PowerupKind::Shockwave | PowerupKind::WeaponUpgrade => {}
+        }
+    }
+
+    pub fn time_decrease(&mut self, amount: f32) {
+        self.speed_boost_time_left = (self.speed_boost_time_left - amount).max(0.);
+        self.time_slow_time_left = (self.time_slow_time_left - amount).max(0.);
+        self.magnet_time_left = (self.magnet_time_left - amount).max(0.);
+    }
+
+    /// Uses up a shield charge if there is one, returns whether the hit was absorbed.
+    pub fn absorb_hit(&mut self) -> bool {
+        if self.shield_hits > 0 {
+            self.shield_hits -= 1;
+            return true;
+        }
+        false
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.speed_boost_time_left > 0. {
+            SPEED_BOOST_MULTIPLIER
+        } else {
+            1.
+        }
+    }
+
+    pub fn enemy_speed_multiplier(&self) -> f32 {
+        if self.time_slow_time_left > 0. {
+            TIME_SLOW_ENEMY_SPEED
+        } else {
+            1.
+        }
+    }
+
+    pub fn magnet_multiplier(&self) -> f32 {
+        if self.magnet_time_left > 0. {
+            MAGNET_RADIUS_MULTIPLIER
+        } else {
+            1.
+        }
+    }
+}
+
+/// Whether something showing a powerup with `time_left` should be in the off half of its warning
+/// flash right now. Always false outside the last `POWERUP_WARNING_TIME` seconds.
+pub fn warning_flash(time_left: f32, elapsed_seconds: f32) -> bool {
+    time_left > 0.
+        && time_left < POWERUP_WARNING_TIME
+        && (elapsed_seconds * WARNING_FLASHES_PER_SECOND).fract() < 0.5
+}
+
+/// Counts powerups and the players' other status timers down every simulation step while playing, so
+/// they last the same no matter how fast the game ticks.
+pub fn update_status_timers(game_state: Res<GameStateInfo>, mut players: Query<&mut PlayerStats>) {
+    if game_state.game_state != GamePlayState::Playing {
+        return;
+    }
+    for mut player_stats in players.iter_mut() {
+        player_stats.powerup_time_decrease(TIME_STEP);
+        player_stats.status_time_decrease(TIME_STEP);
+    }
+}
+
+/// Enemies stop chasing the player while this counts down, so a push from a shockwave isn't
+/// immediately cancelled out by the ai setting their velocity.
+#[derive(Component)]
+pub struct Staggered {
+    pub time_left: f32,
+}
+
+/// Pushes every enemy within range away from `origin` with a rapier impulse, stronger the closer they
+/// are.
+pub fn shockwave(
+    origin: Vec2,
+    commands: &mut Commands,
+    enemies: &Query<(Entity, &Transform, &ReadMassProperties), With<Enemy>>,
+) {
+    for (entity, transform, mass_properties) in enemies.iter() {
+        let offset = transform.translation.truncate() - origin;
+        let distance = offset.length();
+        if distance > SHOCKWAVE_RADIUS || distance <= f32::EPSILON {
+            continue;
+        }
+
+        let strength = 1. - distance / SHOCKWAVE_RADIUS;
+        let speed = SHOCKWAVE_SPEED * (0.3 + strength * 0.7);
+        commands
+            .entity(entity)
+            .insert(ExternalImpulse {
+                impulse: offset / distance * speed * mass_properties.0.mass,
+                torque_impulse: 0.,
+            })
+            .insert(Staggered {
+                time_left: SHOCKWAVE_STAGGER_TIME,
+            });
+    }
+}
@@ -0,0 +1,568 @@
+use crate::daily::DailyChallenge;
+use crate::damage::Hitpoints;
+use crate::definitions_units::{Player, PlayerStats, POWERUP_COLOR};
+use crate::enemy_spawner::Spawner;
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::menu::{MenuAction, MenuActionEvent};
+use crate::net::NetState;
+use crate::powerups::PowerupKind;
+use crate::theme::{PaletteText, ThemedKind};
+use crate::{Score, FONT_ASSET_PATH, HEALTH_FONT_SIZE};
+use bevy::prelude::*;
+use std::fs;
+
+const PROFILE_FILE: &str = "profile.txt";
+const PROFILE_VERSION: u32 = 1; // bump and add a migration whenever the save format changes
+const CREDITS_PER_SCORE: f32 = 0.2;
+
+type Migration = fn(Vec<String>) -> Vec<String>;
+
+/// Upgrades the lines of an older save one version at a time, `MIGRATIONS[n]` turns a version `n + 1`
+/// save into a version `n + 2` one.
+const MIGRATIONS: [Migration; (PROFILE_VERSION - 1) as usize] = [];
+
+/// The ship every player flies, picked in the shop.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShipVariant {
+    Standard,
+    Scout, // faster but fragile
+    Tank,  // slower but takes more hits
+}
+
+impl ShipVariant {
+    const ALL: [ShipVariant; 3] = [ShipVariant::Standard, ShipVariant::Scout, ShipVariant::Tank];
+
+    fn id(&self) -> &'static str {
+        match self {
+            ShipVariant::Standard => "standard",
+            ShipVariant::Scout => "scout",
+            ShipVariant::Tank => "tank",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ShipVariant::Standard => "Standard Ship",
+            ShipVariant::Scout => "Scout Ship",
+            ShipVariant::Tank => "Tank Ship",
+        }
+    }
+
+    fn apply(&self, player_stats: &mut PlayerStats, hitpoints: &mut Hitpoints) {
+        match self {
+            ShipVariant::Standard => {}
+            ShipVariant::Scout => {
+                player_stats.max_speed += 120.;
+                player_stats.speed_per_frame += 4.;
+                hitpoints.max -= 3;
+                hitpoints.current = hitpoints.max;
+            }
+            ShipVariant::Tank => {
+                player_stats.max_speed -= 90.;
+                hitpoints.max += 3;
+                hitpoints.current = hitpoints.max;
+            }
+        }
+    }
+}
+
+/// Something every run starts with, at most one is equipped at a time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StartingPerk {
+    Shield,    // a shield charge
+    HeadStart, // a kill mode pickup's worth of kill mode
+    Overheal,  // health past the max
+}
+
+impl StartingPerk {
+    const ALL: [StartingPerk; 3] = [
+        StartingPerk::Shield,
+        StartingPerk::HeadStart,
+        StartingPerk::Overheal,
+    ];
+
+    fn id(&self) -> &'static str {
+        match self {
+            StartingPerk::Shield => "shield",
+            StartingPerk::HeadStart => "head_start",
+            StartingPerk::Overheal => "overheal",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            StartingPerk::Shield => "Perk: Starting Shield",
+            StartingPerk::HeadStart => "Perk: Head Start",
+            StartingPerk::Overheal => "Perk: Overheal",
+        }
+    }
+
+    fn apply(&self, player_stats: &mut PlayerStats, hitpoints: &mut Hitpoints) {
+        match self {
+            StartingPerk::Shield => player_stats.activate_powerup(PowerupKind::Shield),
+            StartingPerk::HeadStart => player_stats.activate_powerup(PowerupKind::KillMode),
+            StartingPerk::Overheal => hitpoints.heal(3),
+        }
+    }
+}
+
+/// Anything the shop sells.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Unlock {
+    Ship(ShipVariant),
+    Powerup(PowerupKind), // locked powerups don't spawn
+    Perk(StartingPerk),
+}
+
+impl Unlock {
+    /// How the unlock is written in the save, `kind:name`.
+    fn id(&self) -> String {
+        match self {
+            Unlock::Ship(ship) => format!("ship:{}", ship.id()),
+            Unlock::Powerup(kind) => format!("powerup:{}", powerup_id(*kind)),
+            Unlock::Perk(perk) => format!("perk:{}", perk.id()),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Unlock::Ship(ship) => ship.name(),
+            Unlock::Powerup(PowerupKind::Shockwave) => "Shockwave Powerup",
+            Unlock::Powerup(PowerupKind::TimeSlow) => "Time Slow Powerup",
+            Unlock::Powerup(PowerupKind::Magnet) => "Magnet Powerup",
+            Unlock::Powerup(kind) => kind.pickup_text(),
+            Unlock::Perk(perk) => perk.name(),
+        }
+    }
+}
+
+/// How a powerup is written in the save, kept apart from anything shown on screen.
+fn powerup_id(kind: PowerupKind) -> &'static str {
+    match kind {
+        PowerupKind::KillMode => "kill_mode",
+        PowerupKind::SpeedBoost => "speed_boost",
+        PowerupKind::Shield => "shield",
+        PowerupKind::Shockwave => "shockwave",
+        PowerupKind::TimeSlow => "time_slow",
+        PowerupKind::Magnet => "magnet",
+        PowerupKind::WeaponUpgrade => "weapon_upgrade",
+    }
+}
+
+pub struct ShopItem {
+    pub unlock: Unlock,
+    pub cost: u32, // in credits, free items are owned from the start
+}
+
+/// Everything in the shop, in the order it is listed. Powerups that aren't in here are never locked.
+pub const SHOP: [ShopItem; 9] = [
+    ShopItem {
+        unlock: Unlock::Ship(ShipVariant::Standard),
+        cost: 0,
+    },
+    ShopItem {
+        unlock: Unlock::Ship(ShipVariant::Scout),
+        cost: 250,
+    },
+    ShopItem {
+        unlock: Unlock::Ship(ShipVariant::Tank),
+        cost: 250,
+    },
+    ShopItem {
+        unlock: Unlock::Powerup(PowerupKind::Shockwave),
+        cost: 150,
+    },
+    ShopItem {
+        unlock: Unlock::Powerup(PowerupKind::TimeSlow),
+        cost: 200,
+    },
+    ShopItem {
+        unlock: Unlock::Powerup(PowerupKind::Magnet),
+        cost: 100,
+    },
+    ShopItem {
+        unlock: Unlock::Perk(StartingPerk::Shield),
+        cost: 300,
+    },
+    ShopItem {
+        unlock: Unlock::Perk(StartingPerk::HeadStart),
+        cost: 200,
+    },
+    ShopItem {
+        unlock: Unlock::Perk(StartingPerk::Overheal),
+        cost: 150,
+    },
+];
+
+/// What carries over from run to run: credits earned from scores, what they bought and the ship and
+/// perk picked in the shop. Saved to disk as `key value` lines under a `version` line, older saves
+/// are migrated when loaded.
+pub struct Profile {
+    pub credits: u32,
+    unlocked: Vec<Unlock>,
+    ship: ShipVariant,
+    perk: Option<StartingPerk>,
+    pub last_run_credits: u32, // earned by the run that just ended, not saved
+}
+
+impl FromWorld for Profile {
+    fn from_world(_world: &mut World) -> Self {
+        Profile::load()
+    }
+}
+
+impl Profile {
+    fn new() -> Profile {
+        Profile {
+            credits: 0,
+            unlocked: Vec::new(),
+            ship: ShipVariant::Standard,
+            perk: None,
+            last_run_credits: 0,
+        }
+    }
+
+    pub fn load() -> Profile {
+        match fs::read_to_string(PROFILE_FILE) {
+            Ok(contents) => Profile::parse(&contents, &MIGRATIONS),
+            Err(_) => Profile::new(),
+        }
+    }
+
+    /// Reads a save written by this or an older version, the current version being the one the last
+    /// of `migrations` upgrades to.
+    fn parse(contents: &str, migrations: &[Migration]) -> Profile {
+        let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+        let version = match lines.first().and_then(|line| line.strip_prefix("version ")) {
+            Some(version) => version.trim().parse::<u32>().unwrap_or(0),
+            None => 0,
+        };
+        if version == 0 {
+            warn!(
+                "{} has no valid version, starting a new profile",
+                PROFILE_FILE
+            );
+            return Profile::new();
+        }
+        if version as usize > migrations.len() + 1 {
+            // keep what this version understands, unknown lines are skipped below
+            warn!("{} is from a newer version of the game", PROFILE_FILE);
+        }
+        for migration in migrations.iter().skip(version as usize - 1) {
+            lines = migration(lines);
+        }
+
+        let mut profile = Profile::new();
+        for line in lines.iter().skip(1) {
+            let (key, value) = match line.trim().split_once(' ') {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let value = value.trim();
+            match key {
+                "credits" => profile.credits = value.parse::<u32>().unwrap_or(0),
+                "unlock" => {
+                    if let Some(item) = SHOP.iter().find(|item| item.unlock.id() == value) {
+                        profile.unlocked.push(item.unlock);
+                    }
+                }
+                "ship" => {
+                    if let Some(ship) = ShipVariant::ALL.iter().find(|ship| ship.id() == value) {
+                        profile.ship = *ship;
+                    }
+                }
+                "perk" => {
+                    profile.perk = StartingPerk::ALL
+                        .iter()
+                        .copied()
+                        .find(|perk| perk.id() == value)
+                }
+                _ => {}
+            }
+        }
+        profile
+    }
+
+    pub fn save(&self) {
+        if let Err(error) = fs::write(PROFILE_FILE, self.to_save_string()) {
+            warn!("Failed to save profile: {}", error);
+        }
+    }
+
+    fn to_save_string(&self) -> String {
+        let mut contents = format!("version {}\ncredits {}\n", PROFILE_VERSION, self.credits);
+        for unlock in self.unlocked.iter() {
+            contents.push_str(&format!("unlock {}\n", unlock.id()));
+        }
+        contents.push_str(&format!("ship {}\n", self.ship.id()));
+        if let Some(perk) = self.perk {
+            contents.push_str(&format!("perk {}\n", perk.id()));
+        }
+        contents
+    }
+
+    fn owns(&self, item: &ShopItem) -> bool {
+        item.cost == 0 || self.unlocked.contains(&item.unlock)
+    }
+
+    fn is_powerup_unlocked(&self, kind: PowerupKind) -> bool {
+        SHOP.iter()
+            .filter(|item| item.unlock == Unlock::Powerup(kind))
+            .all(|item| self.owns(item))
+    }
+
+    /// Adds the credits a run's score is worth and saves them.
+    fn award(&mut self, score: f32) {
+        self.last_run_credits = (score.max(0.) * CREDITS_PER_SCORE) as u32;
+        self.credits += self.last_run_credits;
+        self.save();
+    }
+
+    /// Buys the item if it isn't owned yet and there are enough credits. Owned ships get flown and
+    /// owned perks get equipped, or unequipped if they already were.
+    fn use_item(&mut self, item: &ShopItem) {
+        if !self.owns(item) {
+            if self.credits < item.cost {
+                return;
+            }
+            self.credits -= item.cost;
+            self.unlocked.push(item.unlock);
+        } else if let Unlock::Perk(perk) = item.unlock {
+            if self.perk == Some(perk) {
+                self.perk = None;
+                self.save();
+                return;
+            }
+        }
+        match item.unlock {
+            Unlock::Ship(ship) => self.ship = ship,
+            Unlock::Perk(perk) => self.perk = Some(perk),
+            Unlock::Powerup(_) => {}
+        }
+        self.save();
+    }
+
+    /// Label of a shop button, what the item costs or whether it is in use.
+    pub fn shop_label(&self, index: usize) -> String {
+        let item = match SHOP.get(index) {
+            Some(item) => item,
+            None => return String::new(),
+        };
+        let in_use = match item.unlock {
+            Unlock::Ship(ship) => self.ship == ship,
+            Unlock::Perk(perk) => self.perk == Some(perk),
+            Unlock::Powerup(_) => false,
+        };
+        let status = if in_use {
+            String::from("Equipped")
+        } else if self.owns(item) {
+            String::from("Owned")
+        } else {
+            format!("{}", item.cost)
+        };
+        format!("{}: {}", item.unlock.name(), status)
+    }
+}
+
+/// Network and daily runs are played with the standard ship, no perk and every powerup, so everyone in
+/// them plays the same game.
+fn uses_profile(net: &NetState, daily: &DailyChallenge) -> bool {
+    !net.is_online() && daily.date.is_none()
+}
+
+/// Pays out credits for the score whenever a run ends.
+pub fn award_credits(
+    mut state_events: EventReader<GamePlayState>,
+    score: Res<Score>,
+    mut profile: ResMut<Profile>,
+) {
+    for state in state_events.iter() {
+        if matches!(state, GamePlayState::Lose | GamePlayState::Win) {
+            profile.award(score.score);
+        }
+    }
+}
+
+/// Fits newly spawned players with the ship and perk picked in the shop.
+pub fn apply_loadout(
+    profile: Res<Profile>,
+    net: Res<NetState>,
+    daily: Res<DailyChallenge>,
+    mut players: Query<(&mut PlayerStats, &mut Hitpoints), Added<Player>>,
+) {
+    if !uses_profile(&net, &daily) {
+        return;
+    }
+    for (mut player_stats, mut hitpoints) in players.iter_mut() {
+        profile.ship.apply(&mut player_stats, &mut hitpoints);
+        if let Some(perk) = profile.perk {
+            perk.apply(&mut player_stats, &mut hitpoints);
+        }
+    }
+}
+
+/// Keeps powerups that haven't been bought from spawning. Weapon upgrades are left to the weapon
+/// setting, see `weapon::apply_weapon_setting`.
+pub fn apply_powerup_unlocks(
+    profile: Res<Profile>,
+    net: Res<NetState>,
+    daily: Res<DailyChallenge>,
+    mut spawner: ResMut<Spawner>,
+) {
+    if !profile.is_changed() && !net.is_changed() && !daily.is_changed() {
+        return;
+    }
+    let uses_profile = uses_profile(&net, &daily);
+    for (kind, weight) in spawner.powerup_weights.iter_mut() {
+        if *kind == PowerupKind::WeaponUpgrade {
+            continue;
+        }
+        *weight = if uses_profile && !profile.is_powerup_unlocked(*kind) {
+            0
+        } else {
+            kind.default_spawn_weight()
+        };
+    }
+}
+
+pub fn handle_shop_actions(
+    mut menu_actions: EventReader<MenuActionEvent>,
+    mut profile: ResMut<Profile>,
+) {
+    for action in menu_actions.iter() {
+        if let MenuAction::ShopItem(index) = action.0 {
+            if let Some(item) = SHOP.get(index) {
+                profile.use_item(item);
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct CreditsEarnedText;
+
+pub fn setup_credits_earned_text(commands: &mut Commands, asset_server: &mut ResMut<AssetServer>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT_ASSET_PATH),
+                    font_size: HEALTH_FONT_SIZE - 10.,
+                    color: POWERUP_COLOR,
+                },
+            )
+            .with_text_alignment(TextAlignment::TOP_CENTER)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Percent(24.),
+                    left: Val::Percent(35.),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(CreditsEarnedText)
+        .insert(PaletteText(ThemedKind::Powerup));
+}
+
+/// Shows what the run that just ended paid out, above the final score.
+pub fn update_credits_earned_text(
+    game_state: Res<GameStateInfo>,
+    profile: Res<Profile>,
+    mut credits_text: Query<(&mut Text, &mut Visibility), With<CreditsEarnedText>>,
+) {
+    let (mut text, mut visibility) = credits_text.single_mut();
+    visibility.is_visible = matches!(
+        game_state.game_state,
+        GamePlayState::Lose | GamePlayState::Win
+    );
+    if profile.is_changed() {
+        text.sections[0].value = format!(
+            "+{} CREDITS ({} TOTAL)",
+            profile.last_run_credits, profile.credits
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_same(profile: &Profile, expected: &Profile) {
+        assert_eq!(profile.credits, expected.credits);
+        assert_eq!(profile.unlocked, expected.unlocked);
+        assert_eq!(profile.ship, expected.ship);
+        assert_eq!(profile.perk, expected.perk);
+    }
+
+    /// Stands in for a future format change, every credits line is worth ten times as much.
+    fn multiply_credits(lines: Vec<String>) -> Vec<String> {
+        lines
+            .into_iter()
+            .map(|line| match line.strip_prefix("credits ") {
+                Some(credits) => format!("credits {}0", credits),
+                None => line,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn saves_load_back() {
+        let mut profile = Profile::new();
+        profile.credits = 420;
+        profile.unlocked = vec![
+            Unlock::Ship(ShipVariant::Tank),
+            Unlock::Powerup(PowerupKind::TimeSlow),
+            Unlock::Perk(StartingPerk::HeadStart),
+        ];
+        profile.ship = ShipVariant::Tank;
+        profile.perk = Some(StartingPerk::HeadStart);
+
+        let contents = profile.to_save_string();
+        assert!(contents.starts_with(&format!("version {}\n", PROFILE_VERSION)));
+        assert_same(&Profile::parse(&contents, &MIGRATIONS), &profile);
+    }
+
+    #[test]
+    fn saves_without_a_version_start_over() {
+        let fresh = Profile::new();
+        let missing = Profile::parse("credits 50\nship tank\n", &MIGRATIONS);
+        assert_same(&missing, &fresh);
+        let zero = Profile::parse("version 0\ncredits 50\nship tank\n", &MIGRATIONS);
+        assert_same(&zero, &fresh);
+        let garbled = Profile::parse("version two\ncredits 50\n", &MIGRATIONS);
+        assert_same(&garbled, &fresh);
+    }
+
+    #[test]
+    fn newer_saves_keep_what_is_understood() {
+        let contents = "version 99\ncredits 50\nhat golden\nunlock ship:scout\nship scout\n";
+        let profile = Profile::parse(contents, &MIGRATIONS);
+        assert_eq!(profile.credits, 50);
+        assert_eq!(profile.unlocked, vec![Unlock::Ship(ShipVariant::Scout)]);
+        assert_eq!(profile.ship, ShipVariant::Scout);
+    }
+
+    #[test]
+    fn older_saves_are_migrated() {
+        let migrations: [Migration; 1] = [multiply_credits];
+        let contents = "version 1\ncredits 5\nunlock ship:scout\n";
+        let profile = Profile::parse(contents, &migrations);
+        assert_eq!(profile.credits, 50);
+        assert_eq!(profile.unlocked, vec![Unlock::Ship(ShipVariant::Scout)]);
+    }
+
+    #[test]
+    fn only_newer_migrations_run() {
+        let migrations: [Migration; 2] = [multiply_credits, multiply_credits];
+
+        let current = Profile::parse("version 3\ncredits 5\n", &migrations);
+        assert_eq!(current.credits, 5);
+        let previous = Profile::parse("version 2\ncredits 5\n", &migrations);
+        assert_eq!(previous.credits, 50);
+        let oldest = Profile::parse("version 1\ncredits 5\n", &migrations);
+        assert_eq!(oldest.credits, 500);
+    }
+}
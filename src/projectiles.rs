@@ -0,0 +1,206 @@
+use crate::boss::Boss;
+use crate::damage::{DamageEvent, DamageSource, Hitpoints};
+use crate::definitions_units::Enemy;
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::menu::{MenuAction, MenuActionEvent};
+use crate::particles::{ParticleEffect, ParticleEvent};
+use crate::players::FrameControls;
+use crate::settings::GameSettings;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+const PROJECTILE_POOL_SIZE: usize = 128;
+const PROJECTILE_LIFETIME: f32 = 3.;
+const PROJECTILE_SIZE: f32 = 10.;
+const PROJECTILE_Z: f32 = 55.; // above enemies, below particles
+
+/// Who fired a projectile and so what it can hit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProjectileTeam {
+    Enemy,                                   // hurts players, see `handle_player_colliding`
+    Player { shooter: Entity, damage: i32 }, // hurts enemies and bosses
+}
+
+/// Send this to fire a projectile from `position`.
+pub struct ProjectileEvent {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub team: ProjectileTeam,
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    active: bool,
+    age: f32,
+    team: ProjectileTeam,
+}
+
+impl Projectile {
+    /// Whether the projectile is in play and can hurt players.
+    pub fn is_hostile(&self) -> bool {
+        self.active && self.team == ProjectileTeam::Enemy
+    }
+
+    /// Takes the projectile out of play, it is parked back in the pool on the next update.
+    pub fn destroy(&mut self) {
+        self.active = false;
+    }
+}
+
+/// Projectiles are spawned once up front and reused in a ring like popups, when every projectile is
+/// in use the oldest one gets recycled. Parked projectiles have no collider so rapier ignores them.
+pub struct ProjectilePool {
+    entities: Vec<Entity>,
+    next: usize,
+}
+
+pub fn setup_projectiles(mut commands: Commands) {
+    let mut entities = Vec::with_capacity(PROJECTILE_POOL_SIZE);
+
+    for _ in 0..PROJECTILE_POOL_SIZE {
+        let entity = commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::ONE),
+                    ..default()
+                },
+                transform: Transform {
+                    translation: Vec3::new(0., 0., PROJECTILE_Z),
+                    scale: Vec3::new(PROJECTILE_SIZE, PROJECTILE_SIZE, 1.),
+                    ..default()
+                },
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(RigidBody::KinematicVelocityBased)
+            .insert(Velocity::zero())
+            .insert(Sensor)
+            .insert(ActiveEvents::COLLISION_EVENTS)
+            .insert(Projectile {
+                active: false,
+                age: 0.,
+                team: ProjectileTeam::Enemy,
+            })
+            .id();
+        entities.push(entity);
+    }
+
+    commands.insert_resource(ProjectilePool { entities, next: 0 });
+}
+
+pub fn handle_projectile_events(
+    mut commands: Commands,
+    mut projectile_events: EventReader<ProjectileEvent>,
+    mut projectile_pool: ResMut<ProjectilePool>,
+    settings: Res<GameSettings>,
+    mut projectiles: Query<(
+        &mut Projectile,
+        &mut Transform,
+        &mut Velocity,
+        &mut Sprite,
+        &mut Visibility,
+    )>,
+) {
+    let palette = settings.palette();
+    for event in projectile_events.iter() {
+        let entity = projectile_pool.entities[projectile_pool.next];
+        projectile_pool.next = (projectile_pool.next + 1) % projectile_pool.entities.len();
+
+        if let Ok((mut projectile, mut transform, mut velocity, mut sprite, mut visibility)) =
+            projectiles.get_mut(entity)
+        {
+            projectile.active = true;
+            projectile.age = 0.;
+            projectile.team = event.team;
+            transform.translation = event.position.extend(PROJECTILE_Z);
+            velocity.linvel = event.velocity;
+            sprite.color = match event.team {
+                ProjectileTeam::Enemy => palette.enemy,
+                ProjectileTeam::Player { .. } => palette.player,
+            };
+            visibility.is_visible = true;
+            // fresh contacts, so a recycled projectile doesn't hit what its last shot was touching
+            commands
+                .entity(entity)
+                .insert(Collider::ball(0.5))
+                .insert(CollidingEntities::default());
+        }
+    }
+}
+
+/// Ages projectiles and parks the ones that ran out of time, got destroyed or belong to a run that
+/// has ended. Through `Pause` and `Upgrade` they keep their velocity and stand still only because
+/// physics is paused outside of `Playing`, so they carry on where they were once the run resumes.
+pub fn update_projectiles(
+    mut commands: Commands,
+    frame_controls: Res<FrameControls>,
+    game_state: Res<GameStateInfo>,
+    mut menu_actions: EventReader<MenuActionEvent>,
+    mut projectiles: Query<(Entity, &mut Projectile, &mut Velocity, &mut Visibility)>,
+) {
+    let run_ended = menu_actions.iter().any(|action| {
+        matches!(
+            action.0,
+            MenuAction::Start | MenuAction::StartDaily | MenuAction::Restart | MenuAction::MainMenu
+        )
+    }) || !matches!(
+        game_state.game_state,
+        GamePlayState::Playing | GamePlayState::Pause | GamePlayState::Upgrade
+    );
+
+    for (entity, mut projectile, mut velocity, mut visibility) in projectiles.iter_mut() {
+        if !visibility.is_visible {
+            continue;
+        }
+        if game_state.game_state == GamePlayState::Playing {
+            projectile.age += frame_controls.delta;
+        }
+        if projectile.age >= PROJECTILE_LIFETIME || run_ended {
+            projectile.active = false;
+        }
+        if projectile.active {
+            continue;
+        }
+
+        visibility.is_visible = false;
+        velocity.linvel = Vec2::ZERO;
+        commands.entity(entity).remove::<Collider>();
+    }
+}
+
+/// Player projectiles damage the first enemy or boss they touch and are used up by it.
+pub fn handle_projectile_hits(
+    game_state: Res<GameStateInfo>,
+    mut projectiles: Query<(&mut Projectile, &Transform, &CollidingEntities)>,
+    targets: Query<&Hitpoints, Or<(With<Enemy>, With<Boss>)>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+) {
+    if game_state.game_state != GamePlayState::Playing {
+        return;
+    }
+    for (mut projectile, transform, colliding_entities) in projectiles.iter_mut() {
+        let (shooter, damage) = match projectile.team {
+            ProjectileTeam::Player { shooter, damage } if projectile.active => (shooter, damage),
+            _ => continue,
+        };
+        let target = colliding_entities.iter().find(|collision| {
+            targets
+                .get(*collision)
+                .is_ok_and(|hitpoints| !hitpoints.is_dead())
+        });
+        if let Some(target) = target {
+            damage_events.send(DamageEvent {
+                target,
+                amount: damage,
+                source: DamageSource::Projectile(shooter),
+            });
+            particle_events.send(ParticleEvent {
+                effect: ParticleEffect::EnemyDeath,
+                position: transform.translation.truncate(),
+                direction: Vec2::ZERO,
+            });
+            projectile.destroy();
+        }
+    }
+}
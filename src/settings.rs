@@ -0,0 +1,139 @@
+use crate::flight::FlightModel;
+use crate::game_mode::GameMode;
+use crate::particles::ParticleBudget;
+use crate::players::MAX_PLAYERS;
+use crate::theme::{ColorPalette, ThemePalette, VisualTheme};
+use crate::weapon::WeaponMode;
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::*;
+
+pub const MUSIC_VOLUME: f64 = 0.2;
+
+/// Typed audio channel for the background music so it can be muted without touching sound effects.
+pub struct MusicChannel;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SettingKind {
+    Mode,
+    Music,
+    SoundEffects,
+    Particles,
+    Theme,
+    ColorPalette,
+    HighContrast,
+    FlightModel,
+    Weapon,
+    Players,
+    Seed,
+}
+
+impl SettingKind {
+    // order the settings show up in the settings menu
+    pub const ALL: [SettingKind; 11] = [
+        SettingKind::Mode,
+        SettingKind::Music,
+        SettingKind::SoundEffects,
+        SettingKind::Particles,
+        SettingKind::Theme,
+        SettingKind::ColorPalette,
+        SettingKind::HighContrast,
+        SettingKind::FlightModel,
+        SettingKind::Weapon,
+        SettingKind::Players,
+        SettingKind::Seed,
+    ];
+}
+
+pub struct GameSettings {
+    pub game_mode: GameMode, // takes effect when the next run starts
+    pub music: bool,
+    pub sound_effects: bool,
+    pub particle_budget: ParticleBudget,
+    pub theme: VisualTheme,
+    pub color_palette: ColorPalette,
+    pub high_contrast: bool,
+    pub flight_model: FlightModel,
+    pub weapon_mode: WeaponMode,
+    pub player_count: usize, // local players, takes effect when the next run starts
+    pub repeat_seed: bool,   // runs reuse the last run's seed so the same spawns come again
+}
+
+impl FromWorld for GameSettings {
+    fn from_world(_world: &mut World) -> Self {
+        GameSettings {
+            game_mode: GameMode::Endless,
+            music: true,
+            sound_effects: true,
+            particle_budget: ParticleBudget::Medium,
+            theme: VisualTheme::Flat,
+            color_palette: ColorPalette::Standard,
+            high_contrast: false,
+            flight_model: FlightModel::Arcade,
+            weapon_mode: WeaponMode::Off,
+            player_count: 1,
+            repeat_seed: false,
+        }
+    }
+}
+
+impl GameSettings {
+    /// Moves the setting on to its next value, wrapping around at the end.
+    pub fn cycle(&mut self, setting: SettingKind) {
+        match setting {
+            SettingKind::Mode => self.game_mode = self.game_mode.next(),
+            SettingKind::Music => self.music = !self.music,
+            SettingKind::SoundEffects => self.sound_effects = !self.sound_effects,
+            SettingKind::Particles => self.particle_budget = self.particle_budget.next(),
+            SettingKind::Theme => self.theme = self.theme.next(),
+            SettingKind::ColorPalette => self.color_palette = self.color_palette.next(),
+            SettingKind::HighContrast => self.high_contrast = !self.high_contrast,
+            SettingKind::FlightModel => self.flight_model = self.flight_model.next(),
+            SettingKind::Weapon => self.weapon_mode = self.weapon_mode.next(),
+            SettingKind::Players => self.player_count = self.player_count % MAX_PLAYERS + 1,
+            SettingKind::Seed => self.repeat_seed = !self.repeat_seed,
+        }
+    }
+
+    /// Colors everything in the game should use, from the theme and the color palette setting.
+    pub fn palette(&self) -> ThemePalette {
+        self.color_palette.palette(self.theme)
+    }
+
+    pub fn label(&self, setting: SettingKind) -> String {
+        match setting {
+            SettingKind::Mode => format!("Mode: {}", self.game_mode.name()),
+            SettingKind::Music => format!("Music: {}", on_off(self.music)),
+            SettingKind::SoundEffects => format!("Sound Effects: {}", on_off(self.sound_effects)),
+            SettingKind::Particles => format!("Particles: {}", self.particle_budget.name()),
+            SettingKind::Theme => format!("Theme: {}", self.theme.name()),
+            SettingKind::ColorPalette => format!("Colors: {}", self.color_palette.name()),
+            SettingKind::HighContrast => format!("High Contrast: {}", on_off(self.high_contrast)),
+            SettingKind::FlightModel => format!("Flight: {}", self.flight_model.name()),
+            SettingKind::Weapon => format!("Weapon: {}", self.weapon_mode.name()),
+            SettingKind::Players => format!("Players: {}", self.player_count),
+            SettingKind::Seed => {
+                let seed = if self.repeat_seed { "Repeat" } else { "New" };
+                format!("Seed: {}", seed)
+            }
+        }
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+pub fn apply_audio_settings(settings: Res<GameSettings>, music: Res<AudioChannel<MusicChannel>>) {
+    if !settings.is_changed() {
+        return;
+    }
+    if settings.music {
+        music.set_volume(MUSIC_VOLUME);
+    } else {
+        music.set_volume(0.);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::definitions_units::{
+    PlayerStats, ENEMY_COLOR, HEALTH_COLOR, PLAYER_COLOR, POWERUP_COLOR,
+};
+use crate::powerups::warning_flash;
+use crate::settings::GameSettings;
+use bevy::prelude::*;
+use bevy::render::texture::DEFAULT_IMAGE_HANDLE;
+
+const INVULNERABLE_FLASHES_PER_SECOND: f32 = 10.;
+const INVULNERABLE_FLASH_ALPHA: f32 = 0.25;
+
+/// What kind of thing a sprite is, so the theme knows which texture and color to give it.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThemedKind {
+    Player,
+    Enemy,
+    Health,
+    Powerup,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VisualTheme {
+    Flat,   // the original plain colored squares
+    Blocks, // the bundled colored block sprites
+    Tinted, // the white block sprite tinted with the flat colors
+}
+
+impl VisualTheme {
+    pub fn next(&self) -> VisualTheme {
+        match self {
+            VisualTheme::Flat => VisualTheme::Blocks,
+            VisualTheme::Blocks => VisualTheme::Tinted,
+            VisualTheme::Tinted => VisualTheme::Flat,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VisualTheme::Flat => "Flat",
+            VisualTheme::Blocks => "Blocks",
+            VisualTheme::Tinted => "Tinted",
+        }
+    }
+
+    pub fn palette(&self) -> ThemePalette {
+        match self {
+            VisualTheme::Flat | VisualTheme::Tinted => ThemePalette {
+                player: PLAYER_COLOR,
+                player_kill_mode: POWERUP_COLOR,
+                enemy: ENEMY_COLOR,
+                health: HEALTH_COLOR,
+                powerup: POWERUP_COLOR,
+            },
+            VisualTheme::Blocks => ThemePalette {
+                player: Color::rgb(0.2, 0.45, 1.0),
+                player_kill_mode: Color::rgb(1.0, 0.85, 0.1),
+                enemy: Color::rgb(0.9, 0.2, 0.2),
+                health: Color::rgb(0.3, 0.9, 0.4),
+                powerup: Color::rgb(1.0, 0.55, 0.1),
+            },
+        }
+    }
+
+    /// The texture for a kind of sprite, the default white image for the flat theme.
+    pub fn texture(
+        &self,
+        kind: ThemedKind,
+        kill_mode: bool,
+        assets: &ThemeAssets,
+    ) -> Handle<Image> {
+        match self {
+            VisualTheme::Flat => DEFAULT_IMAGE_HANDLE.typed(),
+            VisualTheme::Tinted => assets.white_block.clone(),
+            VisualTheme::Blocks => match kind {
+                ThemedKind::Player if kill_mode => assets.yellow_block.clone(),
+                ThemedKind::Player => assets.blue_block.clone(),
+                ThemedKind::Enemy => assets.red_block.clone(),
+                ThemedKind::Health => assets.green_block.clone(),
+                ThemedKind::Powerup => assets.orange_block.clone(),
+            },
+        }
+    }
+
+    /// The color a sprite gets multiplied by. The block textures already carry their color so they are
+    /// left untinted.
+    pub fn sprite_color(&self, kind: ThemedKind, kill_mode: bool) -> Color {
+        match self {
+            VisualTheme::Blocks => Color::WHITE,
+            VisualTheme::Flat | VisualTheme::Tinted => self.palette().color(kind, kill_mode),
+        }
+    }
+}
+
+/// Color vision friendly replacements for the theme colors. Everything other than Standard tints the
+/// white block instead of using the colored block sprites.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorPalette {
+    Standard,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl ColorPalette {
+    pub fn next(&self) -> ColorPalette {
+        match self {
+            ColorPalette::Standard => ColorPalette::Deuteranopia,
+            ColorPalette::Deuteranopia => ColorPalette::Protanopia,
+            ColorPalette::Protanopia => ColorPalette::Tritanopia,
+            ColorPalette::Tritanopia => ColorPalette::Standard,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorPalette::Standard => "Standard",
+            ColorPalette::Deuteranopia => "Deuteranopia",
+            ColorPalette::Protanopia => "Protanopia",
+            ColorPalette::Tritanopia => "Tritanopia",
+        }
+    }
+
+    // colors picked from the Okabe-Ito set, leaning on brightness differences as well as hue
+    pub fn palette(&self, theme: VisualTheme) -> ThemePalette {
+        match self {
+            ColorPalette::Standard => theme.palette(),
+            ColorPalette::Deuteranopia => ThemePalette {
+                player: Color::rgb(0.0, 0.45, 0.7),
+                player_kill_mode: Color::rgb(0.94, 0.89, 0.26),
+                enemy: Color::rgb(0.84, 0.37, 0.0),
+                health: Color::rgb(0.95, 0.95, 0.95),
+                powerup: Color::rgb(0.94, 0.89, 0.26),
+            },
+            ColorPalette::Protanopia => ThemePalette {
+                player: Color::rgb(0.35, 0.7, 0.9),
+                player_kill_mode: Color::rgb(0.94, 0.89, 0.26),
+                enemy: Color::rgb(0.9, 0.45, 0.0),
+                health: Color::rgb(0.95, 0.95, 0.95),
+                powerup: Color::rgb(0.94, 0.89, 0.26),
+            },
+            ColorPalette::Tritanopia => ThemePalette {
+                player: Color::rgb(0.0, 0.62, 0.45),
+                player_kill_mode: Color::rgb(0.94, 0.89, 0.26),
+                enemy: Color::rgb(0.84, 0.2, 0.2),
+                health: Color::rgb(0.95, 0.95, 0.95),
+                powerup: Color::rgb(0.94, 0.89, 0.26),
+            },
+        }
+    }
+}
+
+/// Representative color of every kind for the current theme.
+#[derive(Clone, Copy, Debug)]
+pub struct ThemePalette {
+    pub player: Color,
+    pub player_kill_mode: Color,
+    pub enemy: Color,
+    pub health: Color,
+    pub powerup: Color,
+}
+
+impl ThemePalette {
+    pub fn color(&self, kind: ThemedKind, kill_mode: bool) -> Color {
+        match kind {
+            ThemedKind::Player if kill_mode => self.player_kill_mode,
+            ThemedKind::Player => self.player,
+            ThemedKind::Enemy => self.enemy,
+            ThemedKind::Health => self.health,
+            ThemedKind::Powerup => self.powerup,
+        }
+    }
+}
+
+/// Texture and tint for a sprite given the theme and palette settings.
+pub fn sprite_style(
+    settings: &GameSettings,
+    kind: ThemedKind,
+    kill_mode: bool,
+    assets: &ThemeAssets,
+) -> (Color, Handle<Image>) {
+    let theme = settings.theme;
+    match settings.color_palette {
+        ColorPalette::Standard => (
+            theme.sprite_color(kind, kill_mode),
+            theme.texture(kind, kill_mode, assets),
+        ),
+        _ => {
+            let texture = match theme {
+                VisualTheme::Flat => DEFAULT_IMAGE_HANDLE.typed(),
+                VisualTheme::Blocks | VisualTheme::Tinted => assets.white_block.clone(),
+            };
+            (settings.palette().color(kind, kill_mode), texture)
+        }
+    }
+}
+
+pub struct ThemeAssets {
+    red_block: Handle<Image>,
+    blue_block: Handle<Image>,
+    orange_block: Handle<Image>,
+    yellow_block: Handle<Image>,
+    green_block: Handle<Image>,
+    white_block: Handle<Image>,
+}
+
+impl FromWorld for ThemeAssets {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        ThemeAssets {
+            red_block: asset_server.load("RedBlock.png"),
+            blue_block: asset_server.load("BlueBlock.png"),
+            orange_block: asset_server.load("OrangeBlock.png"),
+            yellow_block: asset_server.load("YellowBlock.png"),
+            green_block: asset_server.load("GreenBlock.png"),
+            white_block: asset_server.load("WhiteBlock.png"),
+        }
+    }
+}
+
+/// Gives newly spawned sprites their theme, restyles everything when the theme setting changes and
+/// keeps every player in sync with their kill mode.
+pub fn apply_theme(
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    theme_assets: Res<ThemeAssets>,
+    mut sprites: Query<(
+        &ThemedKind,
+        ChangeTrackers<ThemedKind>,
+        Option<&PlayerStats>,
+        &mut Sprite,
+        &mut Handle<Image>,
+    )>,
+) {
+    let theme_changed = settings.is_changed();
+    let elapsed = time.seconds_since_startup() as f32;
+
+    for (kind, trackers, player_stats, mut sprite, mut texture) in sprites.iter_mut() {
+        if !theme_changed && !trackers.is_added() && player_stats.is_none() {
+            continue;
+        }
+
+        // the kill mode color blinks off just before kill mode runs out
+        let kill_mode = player_stats.is_some_and(|player_stats| {
+            player_stats.kill_mode && !warning_flash(player_stats.time_left_in_kill_mode, elapsed)
+        });
+        let (mut color, new_texture) = sprite_style(&settings, *kind, kill_mode, &theme_assets);
+        // players blink while enemies can't hurt them
+        if player_stats.is_some_and(PlayerStats::is_invulnerable)
+            && (elapsed * INVULNERABLE_FLASHES_PER_SECOND).fract() < 0.5
+        {
+            color.set_a(INVULNERABLE_FLASH_ALPHA);
+        }
+        if sprite.color != color {
+            sprite.color = color;
+        }
+        if *texture != new_texture {
+            *texture = new_texture;
+        }
+    }
+}
+
+/// Marks text whose color follows the palette color of a kind.
+#[derive(Component)]
+pub struct PaletteText(pub ThemedKind);
+
+pub fn apply_palette_to_text(
+    settings: Res<GameSettings>,
+    mut texts: Query<(&PaletteText, &mut Text)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let palette = settings.palette();
+    for (palette_text, mut text) in texts.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = palette.color(palette_text.0, false);
+        }
+    }
+}
+
+/// Extra shapes drawn on top of sprites in high contrast mode so kinds can be told apart without
+/// relying on color: an outline around everything plus an icon per kind.
+#[derive(Component)]
+pub struct ContrastShape;
+
+const CONTRAST_OUTLINE_COLOR: Color = Color::WHITE;
+const CONTRAST_ICON_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.85);
+
+pub fn apply_high_contrast_shapes(
+    mut commands: Commands,
+    settings: Res<GameSettings>,
+    themed: Query<(Entity, &ThemedKind, ChangeTrackers<ThemedKind>)>,
+    shapes: Query<Entity, With<ContrastShape>>,
+) {
+    let settings_changed = settings.is_changed();
+    if settings_changed {
+        for shape in shapes.iter() {
+            commands.entity(shape).despawn_recursive();
+        }
+    }
+    if !settings.high_contrast {
+        return;
+    }
+
+    for (entity, kind, trackers) in themed.iter() {
+        if !settings_changed && !trackers.is_added() {
+            continue;
+        }
+        commands.entity(entity).with_children(|parent| {
+            // slightly bigger square behind the sprite, showing as a border
+            parent
+                .spawn_bundle(contrast_sprite(
+                    CONTRAST_OUTLINE_COLOR,
+                    Vec2::splat(1.3),
+                    Vec3::new(0., 0., -0.5),
+                    0.,
+                ))
+                .insert(ContrastShape);
+
+            // (size, offset, rotation) of every bar making up the icon
+            let bars: Vec<(Vec2, Vec3, f32)> = match kind {
+                // plus sign
+                ThemedKind::Health => vec![
+                    (Vec2::new(0.7, 0.2), Vec3::new(0., 0., 0.5), 0.),
+                    (Vec2::new(0.2, 0.7), Vec3::new(0., 0., 0.5), 0.),
+                ],
+                // diamond
+                ThemedKind::Powerup => vec![(
+                    Vec2::splat(0.45),
+                    Vec3::new(0., 0., 0.5),
+                    std::f32::consts::FRAC_PI_4,
+                )],
+                // cross
+                ThemedKind::Enemy => vec![
+                    (
+                        Vec2::new(0.8, 0.18),
+                        Vec3::new(0., 0., 0.5),
+                        std::f32::consts::FRAC_PI_4,
+                    ),
+                    (
+                        Vec2::new(0.8, 0.18),
+                        Vec3::new(0., 0., 0.5),
+                        -std::f32::consts::FRAC_PI_4,
+                    ),
+                ],
+                // nose pointing the way the ship is facing
+                ThemedKind::Player => vec![(Vec2::new(0.2, 0.5), Vec3::new(0., 0.25, 0.5), 0.)],
+            };
+            for (size, offset, rotation) in bars {
+                parent
+                    .spawn_bundle(contrast_sprite(CONTRAST_ICON_COLOR, size, offset, rotation))
+                    .insert(ContrastShape);
+            }
+        });
+    }
+}
+
+// the parent sprites are scaled to their size so children work in units of the parent size
+fn contrast_sprite(color: Color, size: Vec2, offset: Vec3, rotation: f32) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(size),
+            ..default()
+        },
+        transform: Transform {
+            translation: offset,
+            rotation: Quat::from_rotation_z(rotation),
+            ..default()
+        },
+        ..default()
+    }
+}
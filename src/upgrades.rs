@@ -0,0 +1,254 @@
+use crate::config::GameConfig;
+use crate::daily::DailyChallenge;
+use crate::damage::Hitpoints;
+use crate::definitions_units::{Player, PlayerStats, MAX_OVERHEAL_HEALTH};
+use crate::enemy_spawner::SpawnRng;
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::menu::{MenuAction, MenuActionEvent};
+use crate::net::NetState;
+use crate::settings::GameSettings;
+use crate::weapon::WeaponMode;
+use crate::{GameTickInfo, TickEvent, FONT_ASSET_PATH, HEALTH_FONT_SIZE, TEXT_COLOR};
+use bevy::prelude::*;
+use rand::seq::IteratorRandom;
+
+pub const OFFERED_UPGRADES: usize = 3;
+
+/// What an upgrade changes, `Upgrade::amount` is in the units of the stat it changes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UpgradeEffect {
+    MaxSpeed,     // pixels per second
+    Acceleration, // speed added every frame of thrust
+    MaxHealth,    // hitpoints, up to what the health bar can show
+    KillModeTime, // seconds added to each kill mode pickup and to the most it can stack up to
+    PickupRadius, // pixels
+    Weapon,       // weapon levels, only offered while the weapon is on
+}
+
+pub struct Upgrade {
+    pub name: &'static str,
+    pub effect: UpgradeEffect,
+    pub amount: f32,
+}
+
+/// Every upgrade that can be offered between waves, each equally likely. Picking the same one again
+/// stacks it.
+pub const UPGRADES: [Upgrade; 6] = [
+    Upgrade {
+        name: "Max Speed +90",
+        effect: UpgradeEffect::MaxSpeed,
+        amount: 90.,
+    },
+    Upgrade {
+        name: "Acceleration +2",
+        effect: UpgradeEffect::Acceleration,
+        amount: 2.,
+    },
+    Upgrade {
+        name: "Max Health +1",
+        effect: UpgradeEffect::MaxHealth,
+        amount: 1.,
+    },
+    Upgrade {
+        name: "Kill Mode +1s",
+        effect: UpgradeEffect::KillModeTime,
+        amount: 1.,
+    },
+    Upgrade {
+        name: "Pickup Radius +60",
+        effect: UpgradeEffect::PickupRadius,
+        amount: 60.,
+    },
+    Upgrade {
+        name: "Weapon Level +1",
+        effect: UpgradeEffect::Weapon,
+        amount: 1.,
+    },
+];
+
+impl Upgrade {
+    fn is_available(&self, weapon_mode: WeaponMode) -> bool {
+        self.effect != UpgradeEffect::Weapon || weapon_mode != WeaponMode::Off
+    }
+
+    fn apply(&self, player_stats: &mut PlayerStats, hitpoints: &mut Hitpoints) {
+        match self.effect {
+            UpgradeEffect::MaxSpeed => player_stats.max_speed += self.amount,
+            UpgradeEffect::Acceleration => player_stats.speed_per_frame += self.amount,
+            UpgradeEffect::MaxHealth => {
+                let amount = self.amount as i32;
+                hitpoints.max = (hitpoints.max + amount).min(MAX_OVERHEAL_HEALTH);
+                hitpoints.overheal_max = hitpoints.overheal_max.max(hitpoints.max);
+                hitpoints.heal(amount);
+            }
+            UpgradeEffect::KillModeTime => player_stats.kill_mode_bonus_time += self.amount,
+            UpgradeEffect::PickupRadius => player_stats.pickup_magnet_radius += self.amount,
+            UpgradeEffect::Weapon => {
+                for _ in 0..self.amount as u32 {
+                    player_stats.weapon.upgrade();
+                }
+            }
+        }
+    }
+}
+
+/// The upgrades on offer while in `GamePlayState::Upgrade`, as indexes into `UPGRADES`.
+#[derive(Default)]
+pub struct UpgradeOffer {
+    pub choices: Vec<usize>,
+}
+
+/// Names of the upgrades picked this run in the order they were picked, shown on the lose screen.
+#[derive(Default)]
+pub struct UpgradeBuild {
+    pub chosen: Vec<&'static str>,
+}
+
+impl UpgradeBuild {
+    /// The build as one line, repeated upgrades counted instead of listed again.
+    fn summary(&self) -> String {
+        let mut counted: Vec<(&str, usize)> = Vec::new();
+        for name in self.chosen.iter() {
+            match counted
+                .iter_mut()
+                .find(|(counted_name, _)| counted_name == name)
+            {
+                Some((_, count)) => *count += 1,
+                None => counted.push((name, 1)),
+            }
+        }
+        counted
+            .iter()
+            .map(|(name, count)| match count {
+                1 => String::from(*name),
+                _ => format!("{} x{}", name, count),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// Stops the run every `upgrade_interval_ticks` ticks to offer a few random upgrades. The choice is
+/// made in the menu, which network sessions don't share, so online runs go without upgrades. Runs
+/// after `handle_player_death`, so a tick that kills the last player loses the run instead of
+/// offering an upgrade to nobody.
+pub fn offer_upgrades(
+    mut tick_events: EventReader<TickEvent>,
+    game_tick_time: Res<GameTickInfo>,
+    config: Res<GameConfig>,
+    settings: Res<GameSettings>,
+    daily: Res<DailyChallenge>,
+    net: Res<NetState>,
+    mut spawn_rng: ResMut<SpawnRng>,
+    mut game_state: ResMut<GameStateInfo>,
+    mut state_events: EventWriter<GamePlayState>,
+    mut offer: ResMut<UpgradeOffer>,
+) {
+    let interval = config.upgrade_interval_ticks;
+    if tick_events.iter().count() == 0 || interval == 0 || net.is_online() {
+        return;
+    }
+    if !game_tick_time.ticks.is_multiple_of(interval)
+        || game_state.game_state != GamePlayState::Playing
+    {
+        return;
+    }
+
+    // drawn from the spawn rng so a repeated seed offers the same upgrades
+    let weapon_mode = WeaponMode::current(&settings, &daily);
+    offer.choices = UPGRADES
+        .iter()
+        .enumerate()
+        .filter(|(_, upgrade)| upgrade.is_available(weapon_mode))
+        .map(|(index, _)| index)
+        .choose_multiple(spawn_rng.rng(), OFFERED_UPGRADES);
+    game_state.change_game_play_state(GamePlayState::Upgrade, &mut state_events);
+}
+
+/// Applies the picked upgrade to every living player and carries on with the run. The build starts
+/// over with every new run.
+pub fn handle_upgrade_choice(
+    mut menu_actions: EventReader<MenuActionEvent>,
+    offer: Res<UpgradeOffer>,
+    mut build: ResMut<UpgradeBuild>,
+    mut game_state: ResMut<GameStateInfo>,
+    mut state_events: EventWriter<GamePlayState>,
+    mut players: Query<(&mut PlayerStats, &mut Hitpoints), With<Player>>,
+) {
+    for action in menu_actions.iter() {
+        match action.0 {
+            MenuAction::Start | MenuAction::StartDaily | MenuAction::Restart => {
+                build.chosen.clear();
+            }
+            MenuAction::ChooseUpgrade(choice) => {
+                if game_state.game_state != GamePlayState::Upgrade {
+                    continue;
+                }
+                let upgrade = match offer.choices.get(choice) {
+                    Some(index) => &UPGRADES[*index],
+                    None => continue,
+                };
+                for (mut player_stats, mut hitpoints) in players.iter_mut() {
+                    if !hitpoints.is_dead() {
+                        upgrade.apply(&mut player_stats, &mut hitpoints);
+                    }
+                }
+                build.chosen.push(upgrade.name);
+                game_state.change_game_play_state(GamePlayState::Playing, &mut state_events);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Label of an upgrade button, empty when fewer upgrades than buttons are on offer.
+pub fn choice_label(offer: &UpgradeOffer, choice: usize) -> &'static str {
+    offer
+        .choices
+        .get(choice)
+        .map_or("", |index| UPGRADES[*index].name)
+}
+
+#[derive(Component)]
+pub struct BuildText;
+
+pub fn setup_build_text(commands: &mut Commands, asset_server: &mut ResMut<AssetServer>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT_ASSET_PATH),
+                    font_size: HEALTH_FONT_SIZE - 10.,
+                    color: TEXT_COLOR,
+                },
+            )
+            .with_text_alignment(TextAlignment::TOP_CENTER)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Percent(50.),
+                    left: Val::Percent(35.),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(BuildText);
+}
+
+/// Lists the run's upgrades under the final score once the run is over.
+pub fn update_build_text(
+    game_state: Res<GameStateInfo>,
+    build: Res<UpgradeBuild>,
+    mut build_text: Query<(&mut Text, &mut Visibility), With<BuildText>>,
+) {
+    let (mut text, mut visibility) = build_text.single_mut();
+    visibility.is_visible = matches!(
+        game_state.game_state,
+        GamePlayState::Lose | GamePlayState::Win
+    ) && !build.chosen.is_empty();
+    if build.is_changed() {
+        text.sections[0].value = format!("BUILD: {}", build.summary());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::boss::Boss;
+use crate::daily::DailyChallenge;
+use crate::damage::Hitpoints;
+use crate::definitions_units::{Enemy, Player, PlayerStats};
+use crate::enemy_spawner::Spawner;
+use crate::game_state::{GamePlayState, GameStateInfo};
+use crate::players::{nearest, FrameControls};
+use crate::powerups::PowerupKind;
+use crate::projectiles::{ProjectileEvent, ProjectileTeam};
+use crate::settings::GameSettings;
+use crate::TIME_STEP;
+use bevy::prelude::*;
+
+const PROJECTILE_SPEED: f32 = 900.;
+const AUTO_FIRE_RANGE: f32 = 650.; // auto fire only shoots at enemies closer than this
+const MAX_WEAPON_LEVEL: u32 = 9;
+const FIRE_RATE_UPGRADE: f32 = 1.25; // fire rate is multiplied by this on fire rate upgrades
+const SPREAD_UPGRADE: f32 = 0.12; // radians the fan of shots widens by for every extra shot
+
+/// Whether and how the players' ships shoot. Kill mode works the same either way.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WeaponMode {
+    Off,   // the original game, kill mode is the only way to hurt enemies
+    Auto,  // shoots at the closest enemy in range on its own
+    Aimed, // shoots straight ahead while the fire key is held
+}
+
+impl WeaponMode {
+    pub const ALL: [WeaponMode; 3] = [WeaponMode::Off, WeaponMode::Auto, WeaponMode::Aimed];
+
+    /// The weapon mode of the run being played. Daily challenges are played without weapons, the
+    /// way the game was originally balanced.
+    pub fn current(settings: &GameSettings, daily: &DailyChallenge) -> WeaponMode {
+        if daily.date.is_some() {
+            WeaponMode::Off
+        } else {
+            settings.weapon_mode
+        }
+    }
+
+    pub fn next(&self) -> WeaponMode {
+        match self {
+            WeaponMode::Off => WeaponMode::Auto,
+            WeaponMode::Auto => WeaponMode::Aimed,
+            WeaponMode::Aimed => WeaponMode::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WeaponMode::Off => "Off",
+            WeaponMode::Auto => "Auto",
+            WeaponMode::Aimed => "Aimed",
+        }
+    }
+}
+
+/// A player's gun, part of `PlayerStats` so it starts over every run. Weapon upgrade pickups raise
+/// the level, which takes turns improving the fire rate, adding a shot to the fan and adding damage.
+#[derive(Clone)]
+pub struct Weapon {
+    pub fire_rate: f32, // shots per second
+    pub shots: u32,     // projectiles per shot, fanned out over the spread
+    pub spread: f32,    // radians between the outermost projectiles of a shot
+    pub damage: i32,
+    pub level: u32,
+    pub cooldown_left: f32,
+}
+
+impl Default for Weapon {
+    fn default() -> Weapon {
+        Weapon {
+            fire_rate: 3.,
+            shots: 1,
+            spread: 0.,
+            damage: 1,
+            level: 0,
+            cooldown_left: 0.,
+        }
+    }
+}
+
+impl Weapon {
+    pub fn upgrade(&mut self) {
+        if self.level >= MAX_WEAPON_LEVEL {
+            return;
+        }
+        match self.level % 3 {
+            0 => self.fire_rate *= FIRE_RATE_UPGRADE,
+            1 => {
+                self.shots += 1;
+                self.spread += SPREAD_UPGRADE;
+            }
+            _ => self.damage += 1,
+        }
+        self.level += 1;
+    }
+
+    /// Directions of the projectiles of one shot aimed along `direction`, spread evenly over the fan.
+    fn shot_directions(&self, direction: Vec2) -> impl Iterator<Item = Vec2> + '_ {
+        (0..self.shots).map(move |shot| {
+            let angle = if self.shots > 1 {
+                self.spread * (shot as f32 / (self.shots - 1) as f32 - 0.5)
+            } else {
+                0.
+            };
+            Vec2::from_angle(angle).rotate(direction)
+        })
+    }
+}
+
+/// Fires every living player's weapon as the weapon setting says, stepped with the rest of the
+/// simulation so network peers shoot the same shots.
+pub fn fire_weapons(
+    game_state: Res<GameStateInfo>,
+    settings: Res<GameSettings>,
+    daily: Res<DailyChallenge>,
+    frame_controls: Res<FrameControls>,
+    mut players: Query<(Entity, &Player, &Transform, &Hitpoints, &mut PlayerStats)>,
+    targets: Query<&Transform, (Or<(With<Enemy>, With<Boss>)>, Without<Player>)>,
+    mut projectile_events: EventWriter<ProjectileEvent>,
+) {
+    let weapon_mode = WeaponMode::current(&settings, &daily);
+    if game_state.game_state != GamePlayState::Playing || weapon_mode == WeaponMode::Off {
+        return;
+    }
+
+    for (entity, player, transform, hitpoints, mut player_stats) in players.iter_mut() {
+        let weapon = &mut player_stats.weapon;
+        weapon.cooldown_left = (weapon.cooldown_left - TIME_STEP).max(0.);
+        if hitpoints.is_dead() || weapon.cooldown_left > 0. {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let direction = match weapon_mode {
+            WeaponMode::Auto => {
+                let target = nearest(
+                    position,
+                    targets
+                        .iter()
+                        .map(|transform| transform.translation.truncate()),
+                );
+                match target {
+                    Some(target) if target.distance(position) <= AUTO_FIRE_RANGE => {
+                        (target - position).normalize_or_zero()
+                    }
+                    _ => continue,
+                }
+            }
+            _ if frame_controls.players[player.id].fire => {
+                (transform.rotation * Vec3::Y).truncate()
+            }
+            _ => continue,
+        };
+
+        weapon.cooldown_left = 1. / weapon.fire_rate;
+        for shot_direction in weapon.shot_directions(direction) {
+            projectile_events.send(ProjectileEvent {
+                position: position + shot_direction * transform.scale.x,
+                velocity: shot_direction * PROJECTILE_SPEED,
+                team: ProjectileTeam::Player {
+                    shooter: entity,
+                    damage: weapon.damage,
+                },
+            });
+        }
+    }
+}
+
+/// Weapon upgrade pickups only spawn while the ships have a weapon to upgrade.
+pub fn apply_weapon_setting(
+    settings: Res<GameSettings>,
+    daily: Res<DailyChallenge>,
+    mut spawner: ResMut<Spawner>,
+) {
+    if !settings.is_changed() && !daily.is_changed() {
+        return;
+    }
+    for (kind, weight) in spawner.powerup_weights.iter_mut() {
+        if *kind == PowerupKind::WeaponUpgrade {
+            *weight = match WeaponMode::current(&settings, &daily) {
+                WeaponMode::Off => 0,
+                _ => kind.default_spawn_weight(),
+            };
+        }
+    }
+}